@@ -109,6 +109,10 @@ fn main() {
                 sub_c_arm = Some("enable");
                 writeln!(zcomp, "{}", line)
             }
+            "(export)" => {
+                sub_c_arm = Some("export");
+                writeln!(zcomp, "{}", line)
+            }
             "(generate-standalone)" => {
                 sub_c_arm = Some("generate-standalone");
                 writeln!(zcomp, "{}", line)
@@ -143,7 +147,7 @@ fn main() {
                 }
                 Some("disable") => {
                     if line.contains("_files") {
-                        writeln!(zcomp, "{}", line.replace("_files", "_user_profiles"))
+                        writeln!(zcomp, "{}", line.replace("_files", "_all_profiles"))
                     } else {
                         writeln!(zcomp, "{}", line)
                     }
@@ -162,6 +166,13 @@ fn main() {
                         writeln!(zcomp, "{}", line)
                     }
                 }
+                Some("export") => {
+                    if line.contains("_files") {
+                        writeln!(zcomp, "{}", line.replace("_files", "_all_profiles"))
+                    } else {
+                        writeln!(zcomp, "{}", line)
+                    }
+                }
                 Some("generate-standalone") => {
                     if line.contains("_files") {
                         writeln!(zcomp, "{}", line.replace("_files", "_all_profiles"))
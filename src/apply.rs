@@ -0,0 +1,347 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for applying a manifest with the desired state of `USER_PROFILE_DIR`
+//!
+//! ```toml
+//! disabled = ["vlc"]
+//! enabled = ["firefox"]
+//!
+//! [files."firefox.local"]
+//! present = ["noblacklist ${HOME}/.mozilla"]
+//! absent = ["net none"]
+//! ```
+
+use crate::cli::CliFormat;
+use crate::disable::{disable, is_disabled, DISABLED_DIR};
+use crate::enable::enable;
+use crate::location::Location;
+use crate::profile::{complete_name, ProfileFlags};
+use crate::profile_stream::{Content, Line, ProfileStream};
+use crate::utils::print_json;
+use crate::{fatal, USER_PROFILE_DIR};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::io::ErrorKind;
+use std::process::exit;
+use std::sync::Arc;
+
+/// The desired state of `USER_PROFILE_DIR`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Manifest {
+    /// Profiles which must be disabled
+    disabled: Vec<String>,
+    /// Profiles which must not be disabled
+    enabled: Vec<String>,
+    files: BTreeMap<String, FileState>,
+}
+
+/// The desired state of a file in `USER_PROFILE_DIR`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileState {
+    /// Lines which must be in the file, missing lines are appended
+    present: Vec<String>,
+    /// Lines which must not be in the file
+    absent: Vec<String>,
+}
+
+/// A change necessary to reach the state of the manifest
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Change {
+    Add { file: String, line: String },
+    Remove { file: String, line: String },
+    Disable { profile: String },
+    Enable { profile: String },
+}
+impl Change {
+    fn describe(&self) -> String {
+        match self {
+            Self::Add { file, line } => format!("{}: add `{}`", file, line),
+            Self::Remove { file, line } => format!("{}: remove `{}`", file, line),
+            Self::Disable { profile } => format!("{}: disable", profile),
+            Self::Enable { profile } => format!("{}: enable", profile),
+        }
+    }
+}
+
+pub fn start(cli: &crate::cli::CliApply, format: CliFormat) {
+    debug!("subcommand: apply");
+
+    let manifest = read_to_string(&cli.manifest)
+        .unwrap_or_else(|err| fatal!("Failed to read '{}': {}", cli.manifest, err));
+    let manifest: Manifest = toml::from_str(&manifest)
+        .unwrap_or_else(|err| fatal!("Invalid manifest '{}': {}", cli.manifest, err));
+
+    let changes = plan(&manifest, &USER_PROFILE_DIR, &DISABLED_DIR);
+
+    if format == CliFormat::Json {
+        print_json(&json!({ "drift": !changes.is_empty(), "changes": changes }));
+    } else if changes.is_empty() {
+        info!("Nothing to do.");
+    } else {
+        for change in &changes {
+            println!("{}", change.describe());
+        }
+    }
+
+    if cli.check {
+        exit(if changes.is_empty() { 0 } else { 1 });
+    }
+
+    perform(&changes);
+}
+
+/// Parse `line` from the manifest into the `Content` of a profile line
+fn parse_line(line: &str) -> Content {
+    line.parse::<Content>()
+        .unwrap_or_else(|_| fatal!("Invalid line in the manifest: {}", line))
+}
+
+/// Compute the changes which are necessary to reach the state of `manifest` in `user_dir`
+fn plan(manifest: &Manifest, user_dir: &Location, disabled_dir: &Location) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for (name, state) in &manifest.files {
+        if name.contains('/') {
+            fatal!("Invalid file name in the manifest: {}", name);
+        }
+        let full_name = complete_name(name, ProfileFlags::DENY_BY_PATH)
+            .unwrap_or_else(|err| fatal!("{}", err))
+            .into_owned();
+        let profile_stream = read_profile_stream(user_dir, &full_name);
+
+        // A new .profile would override the one from /etc/firejail with only these lines.
+        if profile_stream.is_none() && full_name.ends_with(".profile") {
+            fatal!(
+                "{} does not exist in ~/.config/firejail, use `fjp edit {}` to create it.",
+                full_name,
+                name
+            );
+        }
+        let contains = |content: &Content| {
+            profile_stream
+                .as_ref()
+                .map_or(false, |profile_stream| profile_stream.contains(content))
+        };
+
+        for line in &state.present {
+            if !contains(&parse_line(line)) {
+                changes.push(Change::Add {
+                    file: full_name.clone(),
+                    line: line.clone(),
+                });
+            }
+        }
+        for line in &state.absent {
+            if contains(&parse_line(line)) {
+                changes.push(Change::Remove {
+                    file: full_name.clone(),
+                    line: line.clone(),
+                });
+            }
+        }
+    }
+
+    for name in &manifest.disabled {
+        let full_name =
+            complete_name(name, ProfileFlags::DENY_BY_PATH).unwrap_or_else(|err| fatal!("{}", err));
+        if !is_disabled(disabled_dir, &full_name) {
+            changes.push(Change::Disable {
+                profile: full_name.into_owned(),
+            });
+        }
+    }
+    for name in &manifest.enabled {
+        let full_name =
+            complete_name(name, ProfileFlags::DENY_BY_PATH).unwrap_or_else(|err| fatal!("{}", err));
+        if is_disabled(disabled_dir, &full_name) {
+            changes.push(Change::Enable {
+                profile: full_name.into_owned(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Read and parse `full_name` from `user_dir`, `None` if it does not exist
+fn read_profile_stream(user_dir: &Location, full_name: &str) -> Option<ProfileStream> {
+    match read_to_string(user_dir.get_profile_path(full_name)) {
+        Ok(data) => Some(data.parse::<ProfileStream>().unwrap_or_else(|ps| ps)),
+        Err(err) if err.kind() == ErrorKind::NotFound => None,
+        Err(err) => fatal!("Failed to read {}: {}", full_name, err),
+    }
+}
+
+fn perform(changes: &[Change]) {
+    let mut files: BTreeMap<&str, ProfileStream> = BTreeMap::new();
+    for change in changes {
+        match change {
+            Change::Add { file, line } => {
+                let profile_stream = files.entry(file).or_insert_with(|| {
+                    read_profile_stream(&USER_PROFILE_DIR, file)
+                        .unwrap_or_else(|| ProfileStream::from_iter([]))
+                });
+                profile_stream.extend([Line {
+                    lineno: None,
+                    content: Arc::new(parse_line(line)),
+                }]);
+            }
+            Change::Remove { file, line } => {
+                let content = parse_line(line);
+                let profile_stream = files
+                    .remove(file.as_str())
+                    .or_else(|| read_profile_stream(&USER_PROFILE_DIR, file))
+                    .unwrap_or_else(|| ProfileStream::from_iter([]));
+                files.insert(
+                    file,
+                    profile_stream
+                        .into_iter()
+                        .filter(|l| *l.content != content)
+                        .collect(),
+                );
+            }
+            Change::Disable { .. } | Change::Enable { .. } => (),
+        }
+    }
+
+    for (file, profile_stream) in files {
+        let path = USER_PROFILE_DIR.get_profile_path(file);
+        debug!("Write '{}'", path.display());
+        write(&path, profile_stream.to_string())
+            .unwrap_or_else(|err| error!("Failed to write '{}': {}", path.display(), err));
+    }
+
+    for change in changes {
+        match change {
+            // Never prompt, apply is meant to run unattended.
+            Change::Disable { profile } => disable(profile, false),
+            Change::Enable { profile } => enable(profile, false),
+            Change::Add { .. } | Change::Remove { .. } => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use std::os::unix::fs::symlink;
+
+    fn plan_in(tmp: &TempDir, manifest: &str) -> Vec<String> {
+        let manifest: Manifest = toml::from_str(manifest).unwrap();
+        let user_dir = Location::from(tmp.path().join("user"));
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        plan(&manifest, &user_dir, &disabled_dir)
+            .iter()
+            .map(Change::describe)
+            .collect()
+    }
+
+    #[test]
+    fn plan_lines() {
+        let tmp = TempDir::new();
+        tmp.write(
+            "user/foo.local",
+            "noroot
+net none
+",
+        );
+        assert_eq!(
+            plan_in(
+                &tmp,
+                r#"
+                [files."foo.local"]
+                present = ["noroot", "nosound"]
+                absent = ["net none", "nogroups"]
+                "#
+            ),
+            ["foo.local: add `nosound`", "foo.local: remove `net none`",]
+        );
+    }
+
+    #[test]
+    fn plan_missing_local() {
+        let tmp = TempDir::new();
+        assert_eq!(
+            plan_in(
+                &tmp,
+                r#"
+                [files."bar.local"]
+                present = ["noroot"]
+                absent = ["nosound"]
+                "#
+            ),
+            ["bar.local: add `noroot`"]
+        );
+    }
+
+    #[test]
+    fn plan_no_drift() {
+        let tmp = TempDir::new();
+        tmp.write(
+            "user/foo.local",
+            "noroot
+",
+        );
+        tmp.write("user/disabled/vlc.profile", "");
+        assert!(plan_in(
+            &tmp,
+            r#"
+            disabled = ["vlc"]
+            enabled = ["mpv"]
+
+            [files."foo.local"]
+            present = ["noroot"]
+            "#
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn plan_disable_and_enable() {
+        let tmp = TempDir::new();
+        tmp.write("user/disabled/mpv.profile", "");
+        symlink(
+            "/etc/firejail/totem.profile",
+            tmp.path().join("user/disabled/totem.profile"),
+        )
+        .unwrap();
+        assert_eq!(
+            plan_in(
+                &tmp,
+                r#"
+                disabled = ["vlc"]
+                enabled = ["mpv", "totem", "firefox"]
+                "#
+            ),
+            [
+                "vlc.profile: disable",
+                "mpv.profile: enable",
+                "totem.profile: enable",
+            ]
+        );
+    }
+}
@@ -17,11 +17,16 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::profile::{Profile, ProfileFlags};
-use crate::{fatal, utils::ColoredText};
+use crate::cli::CliFormat;
+use crate::config::CONFIG;
+use crate::fatal;
+use crate::profile::{Profile, ProfileFlags, GLOBALS};
+use crate::profile_stream::ProfileStream;
+use crate::utils::{print_json, ColoredText};
 use log::{debug, error, warn};
 use nix::sys::signal::{kill, Signal::SIGTERM};
 use nix::unistd::Pid;
+use serde_json::json;
 use std::convert::TryInto;
 use std::io;
 use std::process::{Child, Command, Stdio};
@@ -33,16 +38,46 @@ struct Options {
     show_redirects: bool,
 }
 
-pub fn start(cli: &crate::cli::CliCat) {
+pub fn start(cli: &crate::cli::CliCat, format: CliFormat) {
     debug!("subcommand: cat");
 
-    let cmd: &[&str] = if cli.no_pager {
-        &["cat"]
+    let opts = Options {
+        show_locals: !cli.no_locals,
+        show_redirects: !cli.no_redirects,
+    };
+    let name = &cli.profile_name;
+    let profile_flags = ProfileFlags::default().with(ProfileFlags::READ);
+
+    if format == CliFormat::Json {
+        let profile = Profile::new(name, profile_flags)
+            .unwrap_or_else(|e| fatal!("Couldn't Read Profile. {}", e));
+        let mut files = Vec::new();
+        process(
+            &profile,
+            profile.raw_data(),
+            &opts,
+            &mut |profile, content| {
+                files.push(json!({
+                    "path": profile.path(),
+                    "lines": content
+                        .parse::<ProfileStream>()
+                        .unwrap_or_else(|profile_stream| profile_stream)
+                        .into_inner(),
+                }));
+            },
+            0,
+        );
+        print_json(&json!({ "files": files }));
+        return;
+    }
+
+    let cmd = if cli.no_pager {
+        vec!["cat".to_string()]
     } else {
-        &["less", "-R"]
+        CONFIG.pager()
     };
 
-    let mut child: Option<Child> = Command::new(cmd[0])
+    let mut child: Option<Child> = Command::new(&cmd[0])
         .args(&cmd[1..])
         .stdin(Stdio::piped())
         .stderr(Stdio::null())
@@ -56,13 +91,6 @@ pub fn start(cli: &crate::cli::CliCat) {
             Some,
         );
 
-    let opts = Options {
-        show_locals: !cli.no_locals,
-        show_redirects: !cli.no_redirects,
-    };
-    let name = &cli.profile_name;
-    let profile_flags = ProfileFlags::default().with(ProfileFlags::READ);
-
     match Profile::new(name, profile_flags) {
         Ok(p) => {
             let mut output: Box<dyn io::Write> = if let Some(ref mut child) = child {
@@ -70,7 +98,13 @@ pub fn start(cli: &crate::cli::CliCat) {
             } else {
                 Box::new(io::stdout())
             };
-            process(&p, p.raw_data(), &opts, &mut output, 0);
+            process(
+                &p,
+                p.raw_data(),
+                &opts,
+                &mut |profile, content| show_file(profile, content, &mut output),
+                0,
+            );
         }
         Err(e) => {
             if let Some(ref child) = child {
@@ -85,11 +119,12 @@ pub fn start(cli: &crate::cli::CliCat) {
     }
 }
 
-fn process<W: io::Write>(
+/// Pass `profile` and, depending on `opts`, its `.local`s and redirect profiles to `show`
+fn process(
     profile: &Profile<'_>,
     content: &str,
     opts: &Options,
-    output: &mut W,
+    show: &mut dyn FnMut(&Profile<'_>, &str),
     mut depth: u8,
 ) {
     if depth >= 16 {
@@ -101,15 +136,15 @@ fn process<W: io::Write>(
 
     if opts.show_locals {
         if let Some(locals) = locals {
-            show_locals(&locals, opts, output);
+            show_locals(&locals, opts, show);
         }
     }
 
-    show_file(profile, content, output);
+    show(profile, content);
 
     if opts.show_redirects {
         if let Some(profiles) = profiles {
-            show_profiles(&profiles, opts, output, depth);
+            show_profiles(&profiles, opts, show, depth);
         }
     }
 }
@@ -143,7 +178,7 @@ fn show_file<W: io::Write>(profile: &Profile<'_>, content: &str, output: &mut W)
         .write_all(
             ColoredText::new(
                 Color::Blue,
-                &format!("# {}:\n", profile.path().unwrap().to_string_lossy()),
+                format!("# {}:\n", profile.path().unwrap().to_string_lossy()),
             )
             .as_bytes(),
         )
@@ -151,26 +186,29 @@ fn show_file<W: io::Write>(profile: &Profile<'_>, content: &str, output: &mut W)
     output.write_all(content.as_bytes()).unwrap();
 }
 
-fn show_locals<W: io::Write>(locals: &[String], _opts: &Options, output: &mut W) {
+fn show_locals(locals: &[String], _opts: &Options, show: &mut dyn FnMut(&Profile<'_>, &str)) {
     locals
         .iter()
-        .filter(|&name| {
-            name != "globals.local" && name != "pre-globals.local" && name != "post-globals.local"
-        })
+        .filter(|name| !GLOBALS.contains(&name.as_str()))
         .filter_map(|name| {
             Profile::new(name, ProfileFlags::default().with(ProfileFlags::READ)).ok()
         })
         .for_each(|profile| {
-            show_file(&profile, profile.raw_data(), output);
+            show(&profile, profile.raw_data());
         });
 }
 
-fn show_profiles<W: io::Write>(profiles: &[String], opts: &Options, output: &mut W, depth: u8) {
+fn show_profiles(
+    profiles: &[String],
+    opts: &Options,
+    show: &mut dyn FnMut(&Profile<'_>, &str),
+    depth: u8,
+) {
     for name in profiles {
         let profile_flags = ProfileFlags::default().with(ProfileFlags::READ);
         match Profile::new(name, profile_flags) {
             Ok(p) => {
-                process(&p, p.raw_data(), opts, output, depth);
+                process(&p, p.raw_data(), opts, show, depth);
             }
             Err(e) => {
                 error!("Couldn't Read profile. {}", e);
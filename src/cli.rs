@@ -22,21 +22,90 @@ use clap::{ArgEnum, Args, Parser, Subcommand};
 #[derive(Debug, Parser)]
 #[clap(version, about)]
 pub struct Cli {
+    #[clap(
+        long,
+        global = true,
+        arg_enum,
+        value_name = "FORMAT",
+        default_value = "text",
+        help = "specify the output format",
+        long_help = concat!(
+            "specify the output format, can be given before or after the subcommand\n",
+            " text: human readable output\n",
+            " json: JSON documents on stdout and JSON lines for log messages on stderr\n",
+        ),
+    )]
+    pub output: CliFormat,
+    #[clap(
+        long = "profile-dir",
+        value_name = "DIR",
+        multiple_occurrences = true,
+        help = "Search profiles in DIR too",
+        long_help = concat!(
+            "Search profiles in DIR too, after ~/.config/firejail and before /etc/firejail. ",
+            "Can be given multiple times, earlier ones are searched first.",
+        ),
+    )]
+    pub profile_dirs: Vec<std::path::PathBuf>,
     #[clap(subcommand)]
     pub subcommand: Subcommands,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CliFormat {
+    Text,
+    Json,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Subcommands {
+    Apply(CliApply),
     Cat(CliCat),
+    ConfigStatus(CliConfigStatus),
+    Coverage(CliCoverage),
     Diff(CliDiff),
     Disable(CliDisable),
     Edit(CliEdit),
     Enable(CliEnable),
+    Export(CliExport),
+    Firecfg(CliFirecfg),
+    Fmt(CliFmt),
     GenerateStandalone(CliGenerateStandalone),
     Has(CliHas),
+    Import(CliImport),
     List(CliList),
+    Lsp(CliLsp),
+    Macros(CliMacros),
+    New(CliNew),
+    Overlay(CliOverlay),
+    Paths(CliPaths),
+    Policy(CliPolicy),
     Rm(CliRm),
+    Shortnames(CliShortnames),
+    Validate(CliValidate),
+    Which(CliWhich),
+    #[clap(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Apply a manifest with the desired state of ~/.config/firejail",
+    long_about = concat!(
+        "Apply a manifest with the desired state of ~/.config/firejail\n",
+        "\n",
+        "The manifest is a TOML file which lists profiles that must be disabled or enabled ",
+        "and lines that must be present in or absent from files in ~/.config/firejail.",
+    ),
+)]
+pub struct CliApply {
+    #[clap(
+        long,
+        help = "Only show the necessary changes, exit with 1 if there are any"
+    )]
+    pub check: bool,
+    #[clap(help = "The manifest to apply.")]
+    pub manifest: String,
 }
 
 #[derive(Debug, Args)]
@@ -52,6 +121,42 @@ pub struct CliCat {
     pub profile_name: String,
 }
 
+#[derive(Debug, Args)]
+#[clap(
+    about = "Show the global features of firejail.config",
+    long_about = concat!(
+        "Show the global features of firejail.config\n",
+        "\n",
+        "With PROFILE_NAMES, warn about commands of the profiles and their includes ",
+        "which rely on a feature that is disabled in firejail.config.",
+    ),
+)]
+pub struct CliConfigStatus {
+    #[clap(help = "The profiles to check.")]
+    pub profile_names: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Report which installed applications are covered by a profile",
+    long_about = concat!(
+        "Report which installed applications are covered by a profile\n",
+        "\n",
+        "Lists the applications from the desktop files without a profile, the applications ",
+        "whose profile only includes default.profile and the profiles for programs which are ",
+        "neither on PATH nor started by a desktop file.",
+    ),
+)]
+pub struct CliCoverage {
+    #[clap(
+        long,
+        value_name = "DIR",
+        default_value = "/usr/share/applications",
+        help = "The directory with the desktop files"
+    )]
+    pub applications_dir: std::path::PathBuf,
+}
+
 #[derive(Debug, Args)]
 #[clap(about = "Show the differences between two profiles")]
 pub struct CliDiff {
@@ -60,12 +165,12 @@ pub struct CliDiff {
         arg_enum,
         help = "specify the diff format",
         long_help = concat!(
-            "specify the diff format\n",
+            "specify the diff format, defaults to the format from the config file or color\n",
             " color: highlight unique lines\n",
             " simple: show unique lines\n",
         ),
     )]
-    pub format: CliDiffFormat,
+    pub format: Option<CliDiffFormat>,
     pub profile_name1: String,
     pub profile_name2: String,
 }
@@ -79,7 +184,13 @@ pub enum CliDiffFormat {
 #[derive(Debug, Args)]
 #[clap(about = "Disable profiles")]
 pub struct CliDisable {
-    #[clap(short, long, exclusive = true, help = "List all disabled profiles")]
+    #[clap(
+        short,
+        long,
+        exclusive = true,
+        help = "List all disabled profiles",
+        long_help = "List all disabled profiles, profiles from /etc/firejail are marked with (system)"
+    )]
     pub list: bool,
     #[clap(
         short,
@@ -90,7 +201,20 @@ pub struct CliDisable {
     )]
     pub user: bool,
     #[clap(
-        required_unless_present_any = &["list", "user"],
+        long,
+        exclusive = true,
+        help = "Disable all .local files",
+        long_help = "Disable all .local files including globals.local, pre-globals.local and post-globals.local"
+    )]
+    pub locals: bool,
+    #[clap(
+        long,
+        exclusive = true,
+        help = "Disable globals.local, pre-globals.local and post-globals.local"
+    )]
+    pub globals: bool,
+    #[clap(
+        required_unless_present_any = &["list", "user", "locals", "globals"],
         help = "The name of the profile to disable",
     )]
     pub profile_name: Option<String>,
@@ -109,8 +233,9 @@ pub struct CliEdit {
     #[clap(
         help = "The name of the profile to edit.",
         long_help = concat!(
-            "The name of the profile to edit. If the profile does not exists,",
-            "it is create except it is found in /etc/firejail, then it is copied from there.",
+            "The name of the profile to edit. If the profile does not exists, ",
+            "it is created from the template of `fjp new` except it is found in /etc/firejail, ",
+            "then it is copied from there.",
         ),
     )]
     pub profile_name: String,
@@ -122,12 +247,110 @@ pub struct CliEnable {
     #[clap(short, long, exclusive = true, help = "Enable ~/.config/firejail")]
     pub user: bool,
     #[clap(
-        required_unless_present = "user",
+        long,
+        exclusive = true,
+        help = "Enable all .local files disabled by `disable --locals`"
+    )]
+    pub locals: bool,
+    #[clap(
+        long,
+        exclusive = true,
+        help = "Enable all .local files disabled by `disable --globals`"
+    )]
+    pub globals: bool,
+    #[clap(
+        required_unless_present_any = &["user", "locals", "globals"],
         help = "The name of the profile to enable"
     )]
     pub profile_name: Option<String>,
 }
 
+#[derive(Debug, Args)]
+#[clap(about = "Export a profile as JSON, YAML or TOML")]
+pub struct CliExport {
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        default_value = "json",
+        help = "The format to export to"
+    )]
+    pub to: CliExportFormat,
+    #[clap(short, long, help = "The name of the file to write results")]
+    pub output_file: Option<String>,
+    #[clap(help = "The name of the profile to export.")]
+    pub profile_name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CliExportFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Inspect the programs sandboxed by firecfg",
+    long_about = concat!(
+        "Inspect the programs sandboxed by firecfg\n",
+        "\n",
+        "firecfg creates symlinks to firejail for the programs listed in firecfg.config ",
+        "and firecfg.d, so they are sandboxed by default.",
+    ),
+)]
+pub struct CliFirecfg {
+    #[clap(subcommand)]
+    pub subcommand: FirecfgSubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum FirecfgSubcommands {
+    Status(CliFirecfgStatus),
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Show which programs are sandboxed by default",
+    long_about = concat!(
+        "Show which programs are sandboxed by default\n",
+        "\n",
+        "Reports installed programs with a profile and a symlink, with a profile but without ",
+        "a symlink, symlinks to programs without a profile or which are not installed and ",
+        "desktop files in ~/.local/share/applications which start their program with firejail.",
+    ),
+)]
+pub struct CliFirecfgStatus {
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "The directory with the symlinks, overrides firecfg.bin-dir from the config"
+    )]
+    pub bin_dir: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Format profiles in the order of firejail's profile.template",
+    long_about = concat!(
+        "Format profiles in the order of firejail's profile.template\n",
+        "\n",
+        "The commands are sorted into the sections of profile.template (includes of .local files, ",
+        "noblacklist, blacklist, mkdir, whitelist, options, private-*, dbus and read-only), ",
+        "comments stay attached to the command they precede. Other includes and commands ",
+        "which fit no section keep their position, nothing is moved across them.",
+    ),
+)]
+pub struct CliFmt {
+    #[clap(
+        long,
+        help = "Do not write the files, exit with 1 if any file is not formatted"
+    )]
+    pub check: bool,
+    #[clap(required = true, help = "The files to format.")]
+    pub files: Vec<String>,
+}
+
 #[derive(Debug, Args)]
 #[clap(about = "Copy the profile and all its includes into one file.")]
 pub struct CliGenerateStandalone {
@@ -149,11 +372,28 @@ pub struct CliHas {
 }
 
 #[derive(Debug, Args)]
-#[clap(about = "List all user profile")]
+#[clap(about = "Import a profile from JSON, YAML or TOML")]
+pub struct CliImport {
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        help = "The format of the file to import",
+        long_help = "The format of the file to import, guessed from its extension if omitted"
+    )]
+    pub from: Option<CliExportFormat>,
+    #[clap(short, long, help = "The name of the file to write the profile to")]
+    pub output_file: Option<String>,
+    #[clap(help = "The file to import.")]
+    pub file: String,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "List profiles")]
 pub struct CliList {
     #[clap(
         long,
-        conflicts_with_all = &["locals", "profiles"],
+        conflicts_with_all = &["locals", "profiles", "orphaned-locals"],
         help = "List only .inc",
     )]
     pub incs: bool,
@@ -165,10 +405,274 @@ pub struct CliList {
     pub locals: bool,
     #[clap(
         long,
-        conflicts_with_all = &["incs", "locals"],
+        conflicts_with_all = &["incs", "locals", "orphaned-locals"],
         help = "List only .profile",
     )]
     pub profiles: bool,
+    #[clap(
+        long,
+        conflicts_with_all = &["all", "disabled"],
+        help = "List profiles from /etc/firejail instead of ~/.config/firejail",
+    )]
+    pub system: bool,
+    #[clap(
+        long,
+        conflicts_with_all = &["system", "disabled"],
+        help = "List profiles from all locations",
+        long_help = "List profiles from ~/.config/firejail, /etc/firejail and ~/.config/firejail/disabled",
+    )]
+    pub all: bool,
+    #[clap(
+        long,
+        conflicts_with_all = &["system", "all"],
+        help = "List disabled profiles instead of ~/.config/firejail",
+    )]
+    pub disabled: bool,
+    #[clap(
+        short,
+        long,
+        help = "Show the status of every profile",
+        long_help = concat!(
+            "Show a table with the status of every profile: present in /etc/firejail, ",
+            "present in ~/.config/firejail, differs from the copy in /etc/firejail, ",
+            "has a .local, is disabled, is a redirect profile ",
+            "and the number of lines which could not be parsed.",
+        ),
+    )]
+    pub long: bool,
+    #[clap(
+        long,
+        help = "List only profiles which exist in ~/.config/firejail and /etc/firejail"
+    )]
+    pub overridden: bool,
+    #[clap(
+        long,
+        help = "List only .local without a .profile or .inc",
+        long_help = concat!(
+            "List only .local files for which neither a .profile nor a .inc exists ",
+            "in ~/.config/firejail or /etc/firejail. globals.local, pre-globals.local ",
+            "and post-globals.local are never orphaned.",
+        ),
+    )]
+    pub orphaned_locals: bool,
+    #[clap(
+        long,
+        conflicts_with_all = &["incs", "locals", "long"],
+        help = "List only redirect profiles and the profiles they include",
+        long_help = concat!(
+            "List only redirect profiles, i.e. .profile files which include another .profile, ",
+            "together with the profiles they include.",
+        ),
+    )]
+    pub redirects: bool,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Start a language server for firejail profiles on stdio",
+    long_about = "Start a language server for firejail profiles on stdio. It provides diagnostics, completion, hover, go-to-definition for includes and formatting."
+)]
+pub struct CliLsp {}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Show the values of the path macros like ${HOME} and ${DOWNLOADS}",
+    long_about = concat!(
+        "Show the values of the path macros like ${HOME} and ${DOWNLOADS}\n",
+        "\n",
+        "${DESKTOP}, ${DOCUMENTS}, ${DOWNLOADS}, ${MUSIC}, ${PICTURES} and ${VIDEOS} ",
+        "are read from ~/.config/user-dirs.dirs.",
+    ),
+)]
+pub struct CliMacros {
+    #[clap(help = "Expand these paths instead of showing all macros.")]
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Create a new profile from the template",
+    long_about = concat!(
+        "Create a new profile from the template\n",
+        "\n",
+        "Writes ~/.config/firejail/NAME.profile with the standard header, the includes of ",
+        "NAME.local and globals.local, the common disable-*.inc and whitelist-*.inc ",
+        "includes and a hardened set of options in the order of profile.template.",
+    ),
+)]
+pub struct CliNew {
+    #[clap(long, help = "Allow 3D acceleration and sound for graphical programs")]
+    pub gui: bool,
+    #[clap(long, help = "Disable networking with net none")]
+    pub no_network: bool,
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "INTERPRETER",
+        multiple_occurrences = true,
+        help = "Allow an interpreter blocked by disable-interpreters.inc",
+        long_help = concat!(
+            "Allow an interpreter blocked by disable-interpreters.inc by including its ",
+            "allow-*.inc and adding it to private-bin. Can be given multiple times.",
+        ),
+    )]
+    pub interpreter: Vec<CliInterpreter>,
+    #[clap(
+        long,
+        requires = "target",
+        conflicts_with = "gui",
+        help = "Create a redirect profile which includes TARGET",
+        long_help = concat!(
+            "Create a redirect profile which includes TARGET, e.g. chromium-common. ",
+            "It has its own NAME.local, --no-network and --interpreter are added as overrides.",
+        ),
+    )]
+    pub redirect: bool,
+    #[clap(long, help = "Overwrite the profile if it exists")]
+    pub force: bool,
+    #[clap(help = "The name of the program")]
+    pub name: String,
+    #[clap(requires = "redirect", help = "The profile to redirect to")]
+    pub target: Option<String>,
+}
+
+/// The interpreters which have an allow-*.inc
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CliInterpreter {
+    Java,
+    Lua,
+    Nodejs,
+    Perl,
+    Php,
+    Python2,
+    Python3,
+    Ruby,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Install shared profiles from an overlay directory",
+    long_about = concat!(
+        "Install shared profiles from an overlay directory\n",
+        "\n",
+        "All .inc, .local and .profile files from the overlay directory are installed into ",
+        "~/.config/firejail. Changes you made to installed files are moved into ",
+        "<NAME>.personal, which is included at the top of the installed file. Removed ",
+        "commands become ignore lines there.",
+    ),
+)]
+pub struct CliOverlay {
+    #[clap(subcommand)]
+    pub subcommand: OverlaySubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum OverlaySubcommands {
+    Status(CliOverlayStatus),
+    Apply(CliOverlayApply),
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Show the status of all files from the overlay")]
+pub struct CliOverlayStatus {
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "The overlay directory, overrides overlay.dir from the config"
+    )]
+    pub overlay: Option<std::path::PathBuf>,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Install or update all files from the overlay")]
+pub struct CliOverlayApply {
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "The overlay directory, overrides overlay.dir from the config"
+    )]
+    pub overlay: Option<std::path::PathBuf>,
+    #[clap(
+        long,
+        help = "Symlink the files instead of copying them, except files with a .personal"
+    )]
+    pub symlink: bool,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Show the files matched by the paths of a profile",
+    long_about = concat!(
+        "Show the files matched by the paths of a profile\n",
+        "\n",
+        "Lists every command with a path (blacklist, whitelist, noblacklist, read-only, ...) ",
+        "of the profile and its includes together with the files on this machine it matches ",
+        "after expanding macros and wildcards.",
+    ),
+)]
+pub struct CliPaths {
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "SWITCH",
+        help = "Assume firejail is started with --SWITCH, decides which conditional lines apply",
+        long_help = concat!(
+            "Assume firejail is started with --SWITCH, decides which conditional lines apply. ",
+            "The conditions from firejail.config (e.g. BROWSER_ALLOW_DRM) are read from the file. ",
+            "Can be given multiple times.\n",
+            " appimage: ?HAS_APPIMAGE\n",
+            " net: ?HAS_NET\n",
+            " nodbus: ?HAS_NODBUS\n",
+            " nosound: ?HAS_NOSOUND\n",
+            " private: ?HAS_PRIVATE\n",
+            " x11: ?HAS_X11\n",
+        ),
+    )]
+    pub assume: Vec<CliSwitch>,
+    #[clap(long, help = "Show only paths which match nothing")]
+    pub unmatched: bool,
+    #[clap(help = "The name of the profile")]
+    pub profile_name: String,
+}
+
+/// The command line switches of firejail which are checked by the `?HAS_*` conditions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CliSwitch {
+    Appimage,
+    Net,
+    Nodbus,
+    Nosound,
+    Private,
+    X11,
+}
+
+#[derive(Debug, Args)]
+#[clap(about = "Check profiles against a baseline policy")]
+pub struct CliPolicy {
+    #[clap(subcommand)]
+    pub subcommand: PolicySubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PolicySubcommands {
+    Check(CliPolicyCheck),
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Check the effective profiles against a policy",
+    long_about = concat!(
+        "Check the effective profiles against a policy\n",
+        "\n",
+        "The effective profile is the profile with all its includes (.local, .inc, ...). ",
+        "Exits with 1 if there are any violations.",
+    ),
+)]
+pub struct CliPolicyCheck {
+    #[clap(long, value_name = "FILE", help = "The policy to check against")]
+    pub policy: String,
+    #[clap(help = "The profiles to check, all profiles if omitted.")]
+    pub profile_names: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -177,3 +681,67 @@ pub struct CliRm {
     #[clap(required = true, help = "The names of the profiles to delete.")]
     pub profile_names: Vec<String>,
 }
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "List the shortnames for profiles",
+    long_about = concat!(
+        "List the shortnames for profiles\n",
+        "\n",
+        "Shortnames can be used instead of a profile name if they are prefixed with a '@', ",
+        "e.g. `fjp cat @dc` shows disable-common.inc. ",
+        "Additional shortnames can be defined in the [shortnames] table of the config file.",
+    ),
+)]
+pub struct CliShortnames {}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Validate profile files",
+    long_about = concat!(
+        "Validate profile files\n",
+        "\n",
+        "Reports invalid lines and includes which can not be found. ",
+        "Exits with 1 if there are any problems.",
+    ),
+)]
+pub struct CliValidate {
+    #[clap(
+        short, long,
+        arg_enum,
+        help = "specify the report format",
+        long_help = concat!(
+            "specify the report format, defaults to the global --output\n",
+            " text: one problem per line\n",
+            " json: JSON document with all problems\n",
+            " sarif: SARIF 2.1.0 for code scanning tools\n",
+        ),
+    )]
+    pub format: Option<CliValidateFormat>,
+    #[clap(required = true, help = "The profile files to validate.")]
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CliValidateFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+#[derive(Debug, Args)]
+#[clap(
+    about = "Show which profile firejail selects for a program",
+    long_about = concat!(
+        "Show which profile firejail selects for a program\n",
+        "\n",
+        "The program is resolved through PATH, skipping firecfg symlinks to firejail. ",
+        "The profile is looked up by the name of the program in ~/.config/firejail and ",
+        "/etc/firejail, falling back to default.profile (server.profile for root). ",
+        "The chain of profiles which are loaded (e.g. redirect profiles) is shown.",
+    ),
+)]
+pub struct CliWhich {
+    #[clap(help = "The name or path of the program.")]
+    pub program: String,
+}
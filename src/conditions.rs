@@ -0,0 +1,144 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for evaluating the conditions of conditional lines (`?HAS_X11: ...`)
+
+use crate::firejail_config::FirejailConfig;
+use crate::profile_stream::{Condition, Content};
+use std::collections::BTreeSet;
+
+/// Decides which conditions are true
+///
+/// `BROWSER_ALLOW_DRM`, `BROWSER_DISABLE_U2F` and `ALLOW_TRAY` come from `firejail.config`,
+/// the `HAS_*` conditions depend on the command line of firejail and must be assumed.
+#[derive(Clone, Debug, Default)]
+pub struct Evaluator {
+    true_conditions: BTreeSet<Condition>,
+}
+impl Evaluator {
+    /// Create an `Evaluator` from `config` and the conditions which are assumed to be true
+    pub fn new(config: &FirejailConfig, assumed: impl IntoIterator<Item = Condition>) -> Self {
+        let mut true_conditions = assumed.into_iter().collect::<BTreeSet<_>>();
+        for (condition, value) in [
+            (Condition::AllowTray, config.allow_tray),
+            (Condition::BrowserAllowDrm, config.browser_allow_drm),
+            (Condition::BrowserDisableU2f, config.browser_disable_u2f),
+        ] {
+            if value {
+                true_conditions.insert(condition);
+            }
+        }
+        Self { true_conditions }
+    }
+
+    /// Create an `Evaluator` from `firejail.config` without assumed conditions
+    pub fn from_config() -> Self {
+        Self::new(&FirejailConfig::load(), [])
+    }
+
+    /// Returns `true` if `condition` is true
+    pub fn evaluate(&self, condition: Condition) -> bool {
+        self.true_conditions.contains(&condition)
+    }
+
+    /// Returns `false` if `content` is a conditional whose condition is false
+    pub fn applies(&self, content: &Content) -> bool {
+        match content {
+            Content::Conditional(conditional) => self.evaluate(conditional.condition()),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Condition; 9] = [
+        Condition::AllowTray,
+        Condition::BrowserAllowDrm,
+        Condition::BrowserDisableU2f,
+        Condition::HasAppimage,
+        Condition::HasNet,
+        Condition::HasNodbus,
+        Condition::HasNosound,
+        Condition::HasPrivate,
+        Condition::HasX11,
+    ];
+
+    /// The conditions `evaluator` says are true
+    fn true_conditions(evaluator: &Evaluator) -> Vec<Condition> {
+        ALL.into_iter()
+            .filter(|&condition| evaluator.evaluate(condition))
+            .collect()
+    }
+
+    #[test]
+    fn evaluate_defaults() {
+        // browser-disable-u2f defaults to yes in firejail.config.
+        assert_eq!(
+            true_conditions(&Evaluator::new(&FirejailConfig::default(), [])),
+            [Condition::BrowserDisableU2f]
+        );
+        assert!(true_conditions(&Evaluator::default()).is_empty());
+    }
+
+    #[test]
+    fn evaluate_assumed() {
+        for condition in ALL {
+            let evaluator = Evaluator::new(
+                &FirejailConfig::parse("browser-disable-u2f no\n"),
+                [condition],
+            );
+            assert_eq!(true_conditions(&evaluator), [condition]);
+        }
+    }
+
+    #[test]
+    fn evaluate_config() {
+        for (config, condition) in [
+            ("allow-tray yes\n", Condition::AllowTray),
+            ("browser-allow-drm yes\n", Condition::BrowserAllowDrm),
+            ("browser-disable-u2f yes\n", Condition::BrowserDisableU2f),
+        ] {
+            let config = FirejailConfig::parse(&format!("browser-disable-u2f no\n{}", config));
+            assert_eq!(true_conditions(&Evaluator::new(&config, [])), [condition]);
+        }
+    }
+
+    #[test]
+    fn applies() {
+        let evaluator = Evaluator::new(&FirejailConfig::default(), [Condition::HasX11]);
+        for (line, applies) in [
+            ("noroot", true),
+            ("# comment", true),
+            ("?HAS_X11: noroot", true),
+            ("?HAS_NET: noroot", false),
+            ("?BROWSER_DISABLE_U2F: nou2f", true),
+            ("?BROWSER_ALLOW_DRM: ignore noexec ${HOME}", false),
+        ] {
+            assert_eq!(
+                evaluator.applies(&line.parse().unwrap()),
+                applies,
+                "{}",
+                line
+            );
+        }
+    }
+}
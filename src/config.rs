@@ -0,0 +1,376 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for the configuration file of fjp (`~/.config/fjp/config.toml`)
+
+use crate::cli::CliDiffFormat;
+use crate::utils::home_dir;
+use clap::ArgEnum;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use nix::unistd::isatty;
+use serde::{de, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env::var_os;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    pub static ref CONFIG: Config = Config::load();
+}
+
+/// The configuration of fjp
+///
+/// All fields are optional, missing fields use the defaults of fjp.
+///
+/// ```toml
+/// editor = "nvim"
+/// pager = "less -R"
+/// color = "auto"
+///
+/// [diff]
+/// format = "simple"
+///
+/// [edit]
+/// auto-copy = true
+///
+/// [profile-dirs]
+/// system = "/etc/firejail"
+/// user = "~/.config/firejail"
+/// extra = ["/srv/team/firejail"]
+///
+/// [shortnames]
+/// ff = "firefox"
+///
+/// [alias]
+/// e = "edit --tmp"
+///
+/// [overlay]
+/// dir = "/srv/team/firejail-overlay"
+/// mode = "copy"
+///
+/// [firecfg]
+/// bin-dir = "/usr/local/bin"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The command used by `edit`, takes precedence over `$EDITOR`
+    pub editor: Option<String>,
+    /// The command used by `cat`
+    pub pager: Option<String>,
+    pub color: ColorMode,
+    pub diff: DiffConfig,
+    pub edit: EditConfig,
+    pub profile_dirs: ProfileDirsConfig,
+    /// Additional shortnames, usable as `@<shortname>`
+    ///
+    /// The values must be plain profile names, i.e. they must not contain a `/`
+    /// or start with a `@`.
+    #[serde(deserialize_with = "deserialize_shortnames")]
+    pub shortnames: BTreeMap<String, String>,
+    /// Aliases for subcommands, e.g. `e = "edit --tmp"`
+    pub alias: BTreeMap<String, String>,
+    pub overlay: OverlayConfig,
+    pub firecfg: FirecfgConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DiffConfig {
+    /// The format used if `diff` is called without `--format`
+    #[serde(deserialize_with = "deserialize_diff_format")]
+    pub format: Option<CliDiffFormat>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct EditConfig {
+    /// Copy the system profile without asking if there is no user profile yet
+    pub auto_copy: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProfileDirsConfig {
+    /// Replaces `/etc/firejail`
+    pub system: Option<PathBuf>,
+    /// Replaces `~/.config/firejail`, a leading `~/` is expanded
+    pub user: Option<PathBuf>,
+    /// Additional directories searched between the user and the system dir,
+    /// a leading `~/` is expanded
+    pub extra: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct OverlayConfig {
+    /// The overlay directory for `overlay`, a leading `~/` is expanded
+    pub dir: Option<PathBuf>,
+    pub mode: OverlayMode,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct FirecfgConfig {
+    /// The directory with the symlinks created by firecfg, default: `/usr/local/bin`
+    pub bin_dir: Option<PathBuf>,
+}
+
+/// How `overlay apply` installs files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayMode {
+    Copy,
+    Symlink,
+}
+impl Default for OverlayMode {
+    fn default() -> Self {
+        Self::Copy
+    }
+}
+
+/// When to use colored output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Only if stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+impl Default for ColorMode {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl Config {
+    /// Read the configuration file, fall back to the defaults if it does not exists or is invalid
+    fn load() -> Self {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+        debug!("config file: {}", path.display());
+
+        match read_to_string(&path) {
+            Ok(data) => Self::parse(&data).unwrap_or_else(|err| {
+                warn!("Invalid config file '{}': {}", path.display(), err);
+                warn!("Continue with the default configuration.");
+                Self::default()
+            }),
+            Err(err) if err.kind() == ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!("Failed to read '{}': {}", path.display(), err);
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(data: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(data)
+    }
+
+    /// Whether colored output should be used
+    pub fn use_color(&self) -> bool {
+        match self.color {
+            ColorMode::Auto => isatty(libc::STDOUT_FILENO).unwrap_or(false),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+
+    /// The editor command, split into program and arguments
+    ///
+    /// Falls back to `$EDITOR` and `vim` if no editor is configured.
+    pub fn editor(&self) -> Vec<String> {
+        if let Some(editor) = self.editor.as_deref().filter(|e| !e.trim().is_empty()) {
+            return split_command(editor);
+        }
+        match var_os("EDITOR").map(|editor| editor.to_string_lossy().into_owned()) {
+            Some(editor) if !editor.trim().is_empty() => split_command(&editor),
+            _ => {
+                warn!("$EDITOR not set or empty, using \"vim\" as fallback.");
+                vec!["vim".to_string()]
+            }
+        }
+    }
+
+    /// The pager command, split into program and arguments
+    ///
+    /// An empty pager disables paging.
+    pub fn pager(&self) -> Vec<String> {
+        let pager = split_command(self.pager.as_deref().unwrap_or("less -R"));
+        if pager.is_empty() {
+            vec!["cat".to_string()]
+        } else {
+            pager
+        }
+    }
+
+    /// The expansion of the alias `name`, split into arguments
+    pub fn alias(&self, name: &str) -> Option<Vec<String>> {
+        self.alias.get(name).map(|alias| split_command(alias))
+    }
+
+    /// The user profile dir with a leading `~/` expanded
+    pub fn user_profile_dir(&self) -> Option<PathBuf> {
+        self.profile_dirs.user.as_deref().map(expand_tilde)
+    }
+
+    /// The overlay dir with a leading `~/` expanded
+    pub fn overlay_dir(&self) -> Option<PathBuf> {
+        self.overlay.dir.as_deref().map(expand_tilde)
+    }
+
+    /// The firecfg bin dir with a leading `~/` expanded
+    pub fn firecfg_bin_dir(&self) -> Option<PathBuf> {
+        self.firecfg.bin_dir.as_deref().map(expand_tilde)
+    }
+
+    /// The additional profile dirs with a leading `~/` expanded
+    pub fn extra_profile_dirs(&self) -> Vec<PathBuf> {
+        self.profile_dirs
+            .extra
+            .iter()
+            .map(|path| expand_tilde(path))
+            .collect()
+    }
+}
+
+/// Return the path of the config file: `$XDG_CONFIG_HOME/fjp/config.toml` or
+/// `~/.config/fjp/config.toml`
+fn config_path() -> Option<PathBuf> {
+    match var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => home_dir().map(|home| home.join(".config")),
+    }
+    .map(|config_dir| config_dir.join("fjp/config.toml"))
+}
+
+fn expand_tilde(path: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home_dir().expect("Can not get User's home dir.").join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+fn split_command(command: &str) -> Vec<String> {
+    command.split_whitespace().map(String::from).collect()
+}
+
+fn deserialize_diff_format<'de, D>(deserializer: D) -> Result<Option<CliDiffFormat>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let format = String::deserialize(deserializer)?;
+    CliDiffFormat::from_str(&format, true)
+        .map(Some)
+        .map_err(de::Error::custom)
+}
+
+fn deserialize_shortnames<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let shortnames = BTreeMap::<String, String>::deserialize(deserializer)?;
+    for (short_name, long_name) in &shortnames {
+        if long_name.contains('/') {
+            return Err(de::Error::custom(format!(
+                "shortname '{}': '{}' must not contain a '/'",
+                short_name, long_name
+            )));
+        }
+        if long_name.starts_with('@') {
+            return Err(de::Error::custom(format!(
+                "shortname '{}': '{}' must not refer to another shortname",
+                short_name, long_name
+            )));
+        }
+    }
+    Ok(shortnames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.color, ColorMode::Auto);
+        assert_eq!(config.diff.format, None);
+        assert!(!config.edit.auto_copy);
+    }
+
+    #[test]
+    fn parse_full() {
+        let config = Config::parse(
+            r#"
+editor = "code --wait"
+pager = "more"
+color = "never"
+
+[diff]
+format = "simple"
+
+[edit]
+auto-copy = true
+
+[profile-dirs]
+system = "/usr/local/etc/firejail"
+
+[firecfg]
+bin-dir = "/opt/bin"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.editor(), ["code", "--wait"]);
+        assert_eq!(config.pager(), ["more"]);
+        assert_eq!(config.color, ColorMode::Never);
+        assert_eq!(config.diff.format, Some(CliDiffFormat::Simple));
+        assert!(config.edit.auto_copy);
+        assert_eq!(
+            config.profile_dirs.system,
+            Some(PathBuf::from("/usr/local/etc/firejail"))
+        );
+        assert_eq!(config.firecfg.bin_dir, Some(PathBuf::from("/opt/bin")));
+    }
+
+    #[test]
+    fn parse_invalid_shortnames() {
+        assert_eq!(
+            Config::parse("[shortnames]\nff = \"firefox\"")
+                .unwrap()
+                .shortnames
+                .get("ff")
+                .map(String::as_str),
+            Some("firefox")
+        );
+        assert!(Config::parse("[shortnames]\nff = \"@dc\"").is_err());
+        assert!(Config::parse("[shortnames]\nff = \"/etc/firejail/firefox\"").is_err());
+    }
+
+    #[test]
+    fn parse_unknown_field() {
+        assert!(Config::parse("editr = \"vim\"").is_err());
+    }
+}
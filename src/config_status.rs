@@ -0,0 +1,134 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for showing the global features of firejail.config
+
+use crate::cli::CliFormat;
+use crate::conditions::Evaluator;
+use crate::effective::effective_profile;
+use crate::firejail_config::FirejailConfig;
+use crate::utils::print_json;
+use log::{debug, error, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// A command which is disabled by a setting in firejail.config
+#[derive(Debug, Serialize)]
+struct DisabledFeature {
+    profile: String,
+    location: String,
+    command: String,
+    feature: &'static str,
+    /// The value of `feature` which disables the command
+    value: bool,
+}
+
+pub fn start(cli: &crate::cli::CliConfigStatus, format: CliFormat) {
+    debug!("subcommand: config-status");
+
+    let config = FirejailConfig::load();
+    let evaluator = Evaluator::new(&config, []);
+
+    let mut disabled = Vec::new();
+    for profile in &cli.profile_names {
+        let lines = match effective_profile(profile, &evaluator) {
+            Ok(lines) => lines,
+            Err(err) => {
+                error!("{}", err);
+                continue;
+            }
+        };
+        disabled.extend(
+            lines
+                .iter()
+                .filter(|line| line.ignored_by.is_none() && evaluator.applies(&line.line.content))
+                .filter_map(|line| {
+                    let command = line.command()?;
+                    let (feature, value) = config.disabled_feature_for(command)?;
+                    Some(DisabledFeature {
+                        profile: profile.clone(),
+                        location: line.location(),
+                        command: command.to_string(),
+                        feature,
+                        value,
+                    })
+                }),
+        );
+    }
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "path": FirejailConfig::path(),
+            "features": config
+                .features()
+                .into_iter()
+                .map(|(name, value, _)| (name, value))
+                .collect::<BTreeMap<_, _>>(),
+            "seccomp-error-action": config
+                .seccomp_error_action
+                .map(|action| action.to_string()),
+            "disabled": disabled,
+        }));
+        return;
+    }
+
+    let features = config.features();
+    let width = features
+        .iter()
+        .map(|(name, ..)| name.len())
+        .chain(Some("seccomp-error-action".len()))
+        .max()
+        .unwrap_or(0);
+    println!("{}", FirejailConfig::path().display());
+    for (name, value, default) in features {
+        let yes_no = |value| if value { "yes" } else { "no" };
+        if value == default {
+            println!("  {:width$}  {}", name, yes_no(value), width = width);
+        } else {
+            println!(
+                "  {:width$}  {} (default: {})",
+                name,
+                yes_no(value),
+                yes_no(default),
+                width = width
+            );
+        }
+    }
+    match config.seccomp_error_action {
+        Some(action) => println!(
+            "  {:width$}  {}",
+            "seccomp-error-action",
+            action,
+            width = width
+        ),
+        None => println!("  {:width$}  EPERM", "seccomp-error-action", width = width),
+    }
+
+    for feature in &disabled {
+        warn!(
+            "{}: `{}` is disabled by `{} {}` in firejail.config ({})",
+            feature.profile,
+            feature.command,
+            feature.feature,
+            if feature.value { "yes" } else { "no" },
+            feature.location
+        );
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for reporting which installed applications are covered by a profile
+
+use crate::cli::CliFormat;
+use crate::effective::LOOKUP_FLAGS;
+use crate::list::read_names;
+use crate::location::Location;
+use crate::profile::Profile;
+use crate::profile_stream::{Command, Content, ProfileStream};
+use crate::utils::print_json;
+use crate::which::{is_executable, is_firejail_symlink, profile_name};
+use crate::{SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::env::{split_paths, var_os};
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// The profiles firejail falls back to, they do not belong to a program
+const FALLBACK_PROFILES: &[&str] = &["default", "server"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Coverage {
+    /// There is a profile for the application
+    Profile,
+    /// The profile of the application only includes the generic `default.profile`
+    DefaultOnly,
+    /// There is no profile, firejail falls back to `default.profile`
+    NoProfile,
+}
+
+#[derive(Debug, Serialize)]
+struct Application {
+    desktop_file: PathBuf,
+    name: String,
+    coverage: Coverage,
+    profile: Option<PathBuf>,
+}
+
+pub fn start(cli: &crate::cli::CliCoverage, format: CliFormat) {
+    debug!("subcommand: coverage");
+
+    let mut seen = BTreeSet::new();
+    let applications = desktop_files(&cli.applications_dir)
+        .into_iter()
+        .filter_map(|(desktop_file, name)| {
+            if !seen.insert(name.clone()) {
+                return None;
+            }
+            let (coverage, profile) = coverage(&name);
+            Some(Application {
+                desktop_file,
+                name,
+                coverage,
+                profile,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut installed = executables_in_path();
+    installed.extend(seen);
+    let not_installed = profile_names([&*USER_PROFILE_DIR, &*SYSTEM_PROFILE_DIR])
+        .into_iter()
+        .filter(|name| !installed.contains(name) && !FALLBACK_PROFILES.contains(&name.as_str()))
+        .collect::<Vec<_>>();
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "applications": applications,
+            "not_installed": not_installed,
+        }));
+        return;
+    }
+
+    let print_applications = |heading: &str, coverage: Coverage| {
+        let mut applications = applications
+            .iter()
+            .filter(|application| application.coverage == coverage)
+            .peekable();
+        if applications.peek().is_none() {
+            return;
+        }
+        println!("{}:", heading);
+        for application in applications {
+            println!(
+                "  {} ({})",
+                application.name,
+                application.desktop_file.display()
+            );
+        }
+    };
+    print_applications("Applications without a profile", Coverage::NoProfile);
+    print_applications(
+        "Applications relying only on default.profile",
+        Coverage::DefaultOnly,
+    );
+    if !not_installed.is_empty() {
+        println!("Profiles for programs which are not installed:");
+        for name in &not_installed {
+            println!("  {}.profile", name);
+        }
+    }
+    println!(
+        "{} of {} applications have a profile.",
+        applications
+            .iter()
+            .filter(|application| application.coverage == Coverage::Profile)
+            .count(),
+        applications.len()
+    );
+}
+
+/// Look up the profile firejail selects for the program `name`
+fn coverage(name: &str) -> (Coverage, Option<PathBuf>) {
+    let full_name = format!("{}.profile", name);
+    let profile = match Profile::new(&full_name, LOOKUP_FLAGS) {
+        Ok(profile) => profile,
+        Err(err) => {
+            debug!("{}: {}", full_name, err);
+            return (Coverage::NoProfile, None);
+        }
+    };
+
+    let coverage = if is_default_only(profile.raw_data()) {
+        Coverage::DefaultOnly
+    } else {
+        Coverage::Profile
+    };
+    (coverage, Some(profile.into_pathbuf()))
+}
+
+/// Check whether the only content of the profile `data` besides the includes of `.local`s
+/// is `include default.profile`
+fn is_default_only(data: &str) -> bool {
+    let profile_stream = data.parse::<ProfileStream>().unwrap_or_else(|ps| ps);
+    let mut contents = profile_stream
+        .iter()
+        .map(|line| &*line.content)
+        .filter(|content| match content {
+            Content::Blank | Content::Comment(_) => false,
+            Content::Command(Command::Include(include)) => !include.ends_with(".local"),
+            _ => true,
+        });
+    matches!(
+        contents.next(),
+        Some(Content::Command(Command::Include(include))) if include == "default.profile"
+    ) && contents.next().is_none()
+}
+
+/// The visible desktop files in `dir` and the profile names of their programs
+fn desktop_files(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut paths = match read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "desktop"))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            warn!("Failed to read {}: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let data = read_to_string(&path).ok()?;
+            let name = parse_desktop_entry(&data)?;
+            Some((path, name))
+        })
+        .collect()
+}
+
+/// Get the `Exec` of the `[Desktop Entry]` group of a desktop file
+///
+/// Returns `None` for hidden entries, the `Exec`s of other groups like
+/// `[Desktop Action new-window]` are ignored.
+pub fn desktop_entry_exec(data: &str) -> Option<&str> {
+    let mut in_entry = false;
+    let mut exec = None;
+    for line in data.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if !in_entry {
+            continue;
+        } else if line == "NoDisplay=true" || line == "Hidden=true" {
+            return None;
+        } else if let Some(value) = line.strip_prefix("Exec=") {
+            exec = Some(value);
+        }
+    }
+    exec
+}
+
+/// Get the profile name of the program started by a desktop entry
+///
+/// Returns `None` for hidden entries. `env VAR=VALUE` and `firejail [OPTIONS]` in front
+/// of the program are skipped.
+fn parse_desktop_entry(data: &str) -> Option<String> {
+    let mut words = desktop_entry_exec(data)?
+        .split_whitespace()
+        .map(|word| word.trim_matches('"'));
+    let mut program = words.next()?;
+    if profile_name(program) == "env" {
+        program = words.find(|word| !word.contains('='))?;
+    }
+    if profile_name(program) == "firejail" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    Some(profile_name(program).to_string())
+}
+
+/// The names of all executables in `PATH`, without symlinks to firejail
+fn executables_in_path() -> BTreeSet<String> {
+    let path = var_os("PATH").unwrap_or_default();
+    split_paths(&path)
+        .filter_map(|dir| read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| !is_firejail_symlink(path) && is_executable(path))
+        .filter_map(|path| Some(profile_name(path.to_str()?).to_string()))
+        .collect()
+}
+
+/// The names of all `.profile`s in `locations` which are not included by another
+/// profile (e.g. `firefox-common`), without `.profile`
+fn profile_names<'a>(locations: impl IntoIterator<Item = &'a Location>) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    let mut included = BTreeSet::new();
+    for location in locations {
+        let profiles = match read_names(location) {
+            Ok(profiles) => profiles,
+            Err(err) => {
+                warn!("Failed to read {}: {}", location.get_ref().display(), err);
+                continue;
+            }
+        };
+        for profile in profiles.iter().filter(|name| name.ends_with(".profile")) {
+            let data = match read_to_string(location.get_profile_path(profile)) {
+                Ok(data) => data,
+                Err(err) => {
+                    warn!("Failed to read {}: {}", profile, err);
+                    continue;
+                }
+            };
+            let profile_stream = data.parse::<ProfileStream>().unwrap_or_else(|ps| ps);
+            included.extend(
+                profile_stream
+                    .iter()
+                    .filter_map(|line| match &*line.content {
+                        Content::Command(Command::Include(include)) => {
+                            include.strip_suffix(".profile").map(str::to_string)
+                        }
+                        _ => None,
+                    }),
+            );
+            names.insert(profile.trim_end_matches(".profile").to_string());
+        }
+    }
+    &names - &included
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        assert_eq!(
+            parse_desktop_entry(
+                "[Desktop Entry]\nName=Firefox\nExec=/usr/lib/firefox/firefox %u\n"
+            ),
+            Some("firefox".to_string())
+        );
+        assert_eq!(
+            parse_desktop_entry("[Desktop Entry]\nExec=env GDK_BACKEND=x11 gimp-2.10 %U\n"),
+            Some("gimp-2.10".to_string())
+        );
+        assert_eq!(
+            parse_desktop_entry("[Desktop Entry]\nExec=firejail --private vlc %U\n"),
+            Some("vlc".to_string())
+        );
+        assert_eq!(
+            parse_desktop_entry(
+                "[Desktop Entry]\nExec=foo.AppImage\n\n[Desktop Action new]\nExec=bar --new\n"
+            ),
+            Some("foo".to_string())
+        );
+        assert_eq!(
+            parse_desktop_entry("[Desktop Entry]\nExec=foo\nNoDisplay=true\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_default_only() {
+        assert!(is_default_only("include default.profile\n"));
+        assert!(is_default_only(
+            "# Firejail profile for foo\ninclude foo.local\ninclude globals.local\n\ninclude default.profile\n"
+        ));
+        assert!(!is_default_only("include foo.local\n"));
+        assert!(!is_default_only("include default.profile\nnoroot\n"));
+        assert!(!is_default_only(
+            "include disable-common.inc\ninclude default.profile\n"
+        ));
+        assert!(!is_default_only("include bar.profile\n"));
+    }
+
+    #[test]
+    fn test_profile_names() {
+        let tmp = TempDir::new();
+        tmp.write("user/foo.profile", "include foo-common.profile\n");
+        tmp.write("user/foo.local", "");
+        tmp.write("system/foo.profile", "");
+        tmp.write("system/foo-common.profile", "noroot\n");
+        tmp.write("system/bar.profile", "include default.profile\n");
+        tmp.write("system/default.profile", "");
+        let locations = [
+            Location::from(tmp.path().join("user")),
+            Location::from(tmp.path().join("system")),
+        ];
+
+        assert_eq!(
+            profile_names(&locations).into_iter().collect::<Vec<_>>(),
+            ["bar", "foo"]
+        );
+    }
+}
@@ -17,17 +17,29 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::cli::CliDiffFormat;
+use crate::cli::{CliDiffFormat, CliFormat};
+use crate::config::CONFIG;
 use crate::fatal;
 use crate::profile::{Profile, ProfileFlags};
 use crate::profile_stream::ProfileStream;
-use crate::utils::ColoredText;
+use crate::utils::{print_json, ColoredText};
+use log::warn;
+use serde_json::json;
 use termcolor::Color;
 
-pub fn start(cli: &crate::cli::CliDiff) {
+pub fn start(cli: &crate::cli::CliDiff, format: CliFormat) {
     let [(profile1, profile1_stream), (profile2, profile2_stream)] = read_and_parse(cli);
 
-    match cli.format {
+    if format == CliFormat::Json {
+        format_json(&profile1, &profile2, &profile1_stream, &profile2_stream);
+        return;
+    }
+
+    match cli
+        .format
+        .or(CONFIG.diff.format)
+        .unwrap_or(CliDiffFormat::Color)
+    {
         CliDiffFormat::Color => {
             format_color(&profile1, &profile2, &profile1_stream, &profile2_stream);
         }
@@ -52,12 +64,26 @@ fn read_and_parse(cli: &crate::cli::CliDiff) -> [(Profile<'_>, ProfileStream); 2
     )
     .unwrap_or_else(|err| fatal!("Failed to read {}: {}", profile2_name, err));
 
-    let profile1_stream = profile1.raw_data().parse::<ProfileStream>().unwrap();
-    let profile2_stream = profile2.raw_data().parse::<ProfileStream>().unwrap();
+    let profile1_stream = parse(&profile1);
+    let profile2_stream = parse(&profile2);
 
     [(profile1, profile1_stream), (profile2, profile2_stream)]
 }
 
+fn parse(profile: &Profile<'_>) -> ProfileStream {
+    profile
+        .raw_data()
+        .parse::<ProfileStream>()
+        .unwrap_or_else(|profile_stream| {
+            warn!(
+                "{} contains {} invalid line(s).",
+                profile.full_name(),
+                profile_stream.errors().as_slice().len()
+            );
+            profile_stream
+        })
+}
+
 fn format_color(
     profile1: &Profile<'_>,
     profile2: &Profile<'_>,
@@ -132,3 +158,29 @@ fn format_simple(
         profile2_unique,
     );
 }
+
+fn format_json(
+    profile1: &Profile<'_>,
+    profile2: &Profile<'_>,
+    profile1_stream: &ProfileStream,
+    profile2_stream: &ProfileStream,
+) {
+    let unique = |profile: &Profile<'_>, stream: &ProfileStream, other_stream: &ProfileStream| {
+        json!({
+            "name": profile.full_name(),
+            "path": profile.path(),
+            "unique": stream
+                .iter()
+                .filter(|l| !l.is_comment())
+                .filter(|l| !other_stream.contains(&l.content))
+                .collect::<Vec<_>>(),
+        })
+    };
+
+    print_json(&json!({
+        "profiles": [
+            unique(profile1, profile1_stream, profile2_stream),
+            unique(profile2, profile2_stream, profile1_stream),
+        ],
+    }));
+}
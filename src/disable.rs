@@ -18,16 +18,20 @@
  */
 
 use crate::{
+    cli::CliFormat,
     fatal,
     location::Location,
-    profile::{Profile, ProfileFlags},
-    utils::input,
+    profile::{Profile, ProfileFlags, GLOBALS},
+    utils::{input, join, print_json},
     USER_PROFILE_DIR,
 };
 use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
-use std::fs::{create_dir, rename};
+use serde_json::json;
+use std::fs::{create_dir, read_dir, rename, symlink_metadata, write};
 use std::io::Result as IoResult;
+use std::os::unix::fs::symlink;
+use std::path::Path;
 
 lazy_static! {
     pub static ref DISABLED_DIR: Location = {
@@ -37,25 +41,98 @@ lazy_static! {
     };
 }
 
-pub fn start(cli: &crate::cli::CliDisable) {
+/// The file in `DISABLED_DIR` which remembers the `.local`s disabled by `disable --locals`
+pub const LOCALS_STATE_FILE: &str = ".locals";
+/// The file in `DISABLED_DIR` which remembers the `.local`s disabled by `disable --globals`
+pub const GLOBALS_STATE_FILE: &str = ".globals";
+
+/// Return the content of the file created in `USER_PROFILE_DIR` to disable `shadowed`,
+/// the system profile or the profile from an additional profile dir named `full_name`.
+///
+/// For `.profile`s it includes `default.profile`, `.inc`s and `.local`s are only
+/// shadowed by a comment.
+pub fn shadow_content(full_name: &str, shadowed: &Path) -> String {
+    let mut content = format!(
+        "# This file was generated by fjp to disable {}.\n\
+         # Use `fjp enable {}` to remove it.\n",
+        shadowed.display(),
+        full_name
+    );
+    if full_name.ends_with(".profile") {
+        content.push_str("include default.profile\n");
+    }
+    content
+}
+
+pub fn start(cli: &crate::cli::CliDisable, format: CliFormat) {
     debug!("subcommand: disable");
 
     if cli.user {
         disable_user();
     } else if cli.list {
-        list().unwrap_or_else(|e| error!("An error occured while listing: {}", e));
+        list(&DISABLED_DIR, format)
+            .unwrap_or_else(|e| error!("An error occured while listing: {}", e));
+    } else if cli.locals {
+        create_disabled_dir();
+        disable_locals(
+            LOCALS_STATE_FILE,
+            |name| name.ends_with(".local"),
+            &USER_PROFILE_DIR,
+            &DISABLED_DIR,
+        );
+    } else if cli.globals {
+        create_disabled_dir();
+        disable_locals(
+            GLOBALS_STATE_FILE,
+            |name| GLOBALS.contains(&name),
+            &USER_PROFILE_DIR,
+            &DISABLED_DIR,
+        );
     } else {
-        match create_dir(&*DISABLED_DIR) {
-            Ok(()) => (),
-            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => (),
-            Err(err) => fatal!("Failed to create the disabled dir: {}", err),
+        disable(cli.profile_name.as_deref().unwrap(), true);
+    }
+}
+
+/// Disable the profile `name` from `USER_PROFILE_DIR`, `EXTRA_PROFILE_DIRS` or
+/// `SYSTEM_PROFILE_DIR`
+///
+/// If `interactive` is false, existing files are never overridden instead of asking.
+pub fn disable(name: &str, interactive: bool) {
+    create_disabled_dir();
+    let profile = Profile::new(
+        name,
+        ProfileFlags::LOOKUP_USER
+            | ProfileFlags::LOOKUP_EXTRA
+            | ProfileFlags::LOOKUP_SYSTEM
+            | ProfileFlags::DENY_BY_PATH,
+    )
+    .unwrap_or_else(|err| fatal!("{}", err));
+    match profile.path() {
+        Some(path) if path.starts_with(&*USER_PROFILE_DIR) => {
+            disable_user_profile(profile.full_name(), path, &DISABLED_DIR, interactive)
+        }
+        // Profiles from the additional profile dirs are shadowed like the system profiles.
+        Some(path) => {
+            disable_system_profile(profile.full_name(), path, &USER_PROFILE_DIR, &DISABLED_DIR)
         }
-        let profile = Profile::new(
-            cli.profile_name.as_deref().unwrap(),
-            ProfileFlags::LOOKUP_USER | ProfileFlags::DENY_BY_PATH,
-        )
-        .unwrap();
-        disable_profile(&profile);
+        None => error!(
+            "Could not find '{}' in ~/.config/firejail, the additional profile dirs \
+             or /etc/firejail",
+            profile.full_name()
+        ),
+    }
+}
+
+/// Check whether the profile `full_name` is disabled in `disabled_dir`
+pub fn is_disabled(disabled_dir: &Location, full_name: &str) -> bool {
+    symlink_metadata(disabled_dir.get_profile_path(full_name)).is_ok()
+}
+
+fn create_disabled_dir() {
+    match create_dir(&*DISABLED_DIR) {
+        Ok(()) => (),
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => (),
+        Err(err) => fatal!("Failed to create the disabled dir: {}", err),
     }
 }
 
@@ -70,30 +147,70 @@ fn disable_user() {
         .unwrap_or_else(|e| error!("Rename failed: {}", e));
 }
 
-fn list() -> IoResult<()> {
-    for entry in DISABLED_DIR.get_ref().read_dir()? {
-        println!("{}", entry?.file_name().to_string_lossy());
+fn list(disabled_dir: &Location, format: CliFormat) -> IoResult<()> {
+    let disabled_profiles = disabled_profiles(disabled_dir)?;
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "disabled": disabled_profiles
+                .iter()
+                .map(|(name, is_system)| json!({
+                    "name": name,
+                    "location": if *is_system { "system" } else { "user" },
+                }))
+                .collect::<Vec<_>>(),
+        }));
+        return Ok(());
+    }
+
+    for (name, is_system) in disabled_profiles {
+        if is_system {
+            println!("{} (system)", name);
+        } else {
+            println!("{}", name);
+        }
     }
 
     Ok(())
 }
 
-fn disable_profile(profile: &Profile<'_>) {
-    let enabled_profile = if let Some(path) = profile.path() {
-        path
-    } else {
-        error!(
-            "Could not find '{}' in ~/.config/firejail",
-            profile.full_name()
-        );
-        return;
-    };
+/// The names of the profiles in `disabled_dir` and whether they are system profiles
+fn disabled_profiles(disabled_dir: &Location) -> IoResult<Vec<(String, bool)>> {
+    let mut disabled_profiles = Vec::new();
+    for entry in disabled_dir.get_ref().read_dir()? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        disabled_profiles.push((name, entry.file_type()?.is_symlink()));
+    }
+    disabled_profiles.sort_unstable();
+    Ok(disabled_profiles)
+}
 
-    let disabled_profile = DISABLED_DIR.get_profile_path(profile.full_name());
+/// Disable a profile from `USER_PROFILE_DIR` by moving it into `disabled_dir`
+fn disable_user_profile(
+    full_name: &str,
+    enabled_profile: &Path,
+    disabled_dir: &Location,
+    interactive: bool,
+) {
+    let disabled_profile = disabled_dir.get_profile_path(full_name);
+
+    if let Ok(metadata) = symlink_metadata(&disabled_profile) {
+        if metadata.file_type().is_symlink() {
+            warn!(
+                "Profile '{}' is already disabled in /etc/firejail.",
+                full_name
+            );
+            return;
+        }
+    }
 
     if disabled_profile.exists() {
-        warn!("Profile '{}' is alread disabled.", profile.full_name());
-        if input("Override? [Y/n] ").unwrap() != "y" {
+        warn!("Profile '{}' is alread disabled.", full_name);
+        if !interactive || input("Override? [Y/n] ").unwrap() != "y" {
             info!("Skipping");
             return;
         }
@@ -106,3 +223,224 @@ fn disable_profile(profile: &Profile<'_>) {
     );
     rename(enabled_profile, &disabled_profile).unwrap_or_else(|e| error!("Rename failed: {}", e));
 }
+
+/// Disable a profile from `SYSTEM_PROFILE_DIR` or `EXTRA_PROFILE_DIRS`
+///
+/// A file with the same name is created in `user_dir`, so firejail uses it instead
+/// (see [`shadow_content`]). A symlink to the system profile in `disabled_dir` marks the
+/// profile as disabled, so [`enable`](crate::enable) knows what to remove.
+fn disable_system_profile(
+    full_name: &str,
+    system_profile: &Path,
+    user_dir: &Location,
+    disabled_dir: &Location,
+) {
+    if full_name == "default.profile" {
+        error!("default.profile can not be disabled, firejail needs it as fallback.");
+        return;
+    }
+
+    let disabled_profile = disabled_dir.get_profile_path(full_name);
+    if symlink_metadata(&disabled_profile).is_ok() {
+        error!(
+            "'{}' is already disabled, enable it first to disable {}.",
+            full_name,
+            system_profile.display()
+        );
+        return;
+    }
+
+    let shadow_profile = user_dir.get_profile_path(full_name);
+    debug!("Create '{}'", shadow_profile.display());
+    if let Err(err) = write(&shadow_profile, shadow_content(full_name, system_profile)) {
+        error!("Failed to create '{}': {}", shadow_profile.display(), err);
+        return;
+    }
+
+    debug!(
+        "Symlink '{}' to '{}'",
+        disabled_profile.display(),
+        system_profile.display()
+    );
+    symlink(system_profile, &disabled_profile)
+        .unwrap_or_else(|e| error!("Failed to create symlink: {}", e));
+}
+
+/// Move all `.local`s from `user_dir` for which `filter` returns true into
+/// `disabled_dir` and write their names to `state_file`
+///
+/// `.local`s which are already disabled are skipped, so `enable --locals` or
+/// `enable --globals` restores exactly the set of files disabled here.
+pub fn disable_locals<F: Fn(&str) -> bool>(
+    state_file: &str,
+    filter: F,
+    user_dir: &Location,
+    disabled_dir: &Location,
+) {
+    let state_file = disabled_dir.get_profile_path(state_file);
+    if state_file.exists() {
+        error!(
+            "There are already disabled .local files remembered in '{}', enable them first.",
+            state_file.display()
+        );
+        return;
+    }
+
+    let mut locals = read_dir(user_dir)
+        .unwrap_or_else(|err| fatal!("Failed to open the user profile directory: {}", err))
+        .filter_map(|readdir_result| match readdir_result {
+            Ok(direntry) => Some(direntry),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .filter(|direntry| direntry.file_type().map_or(false, |ft| ft.is_file()))
+        .filter_map(|direntry| direntry.file_name().into_string().ok())
+        .filter(|name| filter(name))
+        .collect::<Vec<_>>();
+    locals.sort_unstable();
+
+    let mut disabled_locals = Vec::with_capacity(locals.len());
+    for name in locals {
+        let disabled_local = disabled_dir.get_profile_path(&name);
+        if symlink_metadata(&disabled_local).is_ok() {
+            warn!("'{}' is already disabled, skipping it.", name);
+            continue;
+        }
+
+        let enabled_local = user_dir.get_profile_path(&name);
+        debug!(
+            "Move '{}' to '{}'",
+            enabled_local.display(),
+            disabled_local.display()
+        );
+        match rename(&enabled_local, &disabled_local) {
+            Ok(()) => disabled_locals.push(name),
+            Err(err) => error!("Failed to disable '{}': {}", name, err),
+        }
+    }
+
+    if disabled_locals.is_empty() {
+        info!("No .local files to disable.");
+        return;
+    }
+
+    if let Err(err) = write(&state_file, join('\n', &disabled_locals) + "\n") {
+        error!(
+            "Failed to write '{}': {}. Disabled: {}",
+            state_file.display(),
+            err,
+            join(", ", &disabled_locals)
+        );
+        return;
+    }
+
+    info!("Disabled {} .local files.", disabled_locals.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use std::fs::{create_dir_all, read_link, read_to_string};
+
+    fn dirs(tmp: &TempDir) -> (Location, Location) {
+        let user_dir = Location::from(tmp.path().join("user"));
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        create_dir_all(&disabled_dir).unwrap();
+        (user_dir, disabled_dir)
+    }
+
+    #[test]
+    fn disable_system_profile_shadows_it() {
+        let tmp = TempDir::new();
+        let (user_dir, disabled_dir) = dirs(&tmp);
+        let system_profile = tmp.write("system/foo.profile", "noroot\n");
+        let system_inc = tmp.write("system/foo.inc", "noroot\n");
+
+        disable_system_profile("foo.profile", &system_profile, &user_dir, &disabled_dir);
+        disable_system_profile("foo.inc", &system_inc, &user_dir, &disabled_dir);
+
+        let shadow = read_to_string(user_dir.get_profile_path("foo.profile")).unwrap();
+        assert_eq!(shadow, shadow_content("foo.profile", &system_profile));
+        assert!(shadow.ends_with("include default.profile\n"));
+        let shadow = read_to_string(user_dir.get_profile_path("foo.inc")).unwrap();
+        assert_eq!(shadow, shadow_content("foo.inc", &system_inc));
+        assert!(!shadow.contains("include"));
+        assert_eq!(
+            read_link(disabled_dir.get_profile_path("foo.profile")).unwrap(),
+            system_profile
+        );
+        assert!(is_disabled(&disabled_dir, "foo.profile"));
+        assert_eq!(read_to_string(&system_profile).unwrap(), "noroot\n");
+    }
+
+    #[test]
+    fn disable_system_profile_keeps_existing_state() {
+        let tmp = TempDir::new();
+        let (user_dir, disabled_dir) = dirs(&tmp);
+        let default_profile = tmp.write("system/default.profile", "noroot\n");
+        disable_system_profile(
+            "default.profile",
+            &default_profile,
+            &user_dir,
+            &disabled_dir,
+        );
+        assert!(!user_dir.get_profile_path("default.profile").exists());
+        assert!(!is_disabled(&disabled_dir, "default.profile"));
+
+        // A disabled user profile with the same name is not replaced by a marker
+        let system_profile = tmp.write("system/foo.profile", "noroot\n");
+        tmp.write("user/disabled/foo.profile", "quiet\n");
+        disable_system_profile("foo.profile", &system_profile, &user_dir, &disabled_dir);
+        assert!(!user_dir.get_profile_path("foo.profile").exists());
+        assert_eq!(
+            read_to_string(disabled_dir.get_profile_path("foo.profile")).unwrap(),
+            "quiet\n"
+        );
+    }
+
+    #[test]
+    fn disable_user_profile_moves_it() {
+        let tmp = TempDir::new();
+        let (_, disabled_dir) = dirs(&tmp);
+        let user_profile = tmp.write("user/foo.profile", "noroot\n");
+
+        disable_user_profile("foo.profile", &user_profile, &disabled_dir, false);
+        assert!(!user_profile.exists());
+        assert_eq!(
+            read_to_string(disabled_dir.get_profile_path("foo.profile")).unwrap(),
+            "noroot\n"
+        );
+
+        // Never override a disabled profile without asking
+        let user_profile = tmp.write("user/foo.profile", "quiet\n");
+        disable_user_profile("foo.profile", &user_profile, &disabled_dir, false);
+        assert!(user_profile.exists());
+        assert_eq!(
+            read_to_string(disabled_dir.get_profile_path("foo.profile")).unwrap(),
+            "noroot\n"
+        );
+    }
+
+    #[test]
+    fn disabled_profiles_tells_user_and_system_apart() {
+        let tmp = TempDir::new();
+        let (user_dir, disabled_dir) = dirs(&tmp);
+        let system_profile = tmp.write("system/foo.profile", "noroot\n");
+        let user_profile = tmp.write("user/bar.profile", "noroot\n");
+        tmp.write("user/disabled/.locals", "bar.local\n");
+
+        disable_system_profile("foo.profile", &system_profile, &user_dir, &disabled_dir);
+        disable_user_profile("bar.profile", &user_profile, &disabled_dir, false);
+
+        assert_eq!(
+            disabled_profiles(&disabled_dir).unwrap(),
+            [
+                ("bar.profile".to_string(), false),
+                ("foo.profile".to_string(), true),
+            ]
+        );
+    }
+}
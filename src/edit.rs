@@ -19,14 +19,14 @@
 
 #![allow(clippy::unreadable_literal)] // bitflags are easier to read without underscores!!
 
+use crate::config::CONFIG;
 use crate::fatal;
+use crate::new::{template, Template};
 use crate::profile::{Profile, ProfileFlags};
 use crate::utils::input;
 use bitflags::bitflags;
 use log::{debug, warn};
-use std::env::var_os;
-use std::ffi::OsString;
-use std::fs::{copy as copy_file, remove_file, rename};
+use std::fs::{copy as copy_file, create_dir_all, remove_file, rename, write};
 use std::path::Path;
 use std::process::Command;
 
@@ -45,6 +45,9 @@ pub fn start(cli: &crate::cli::CliEdit) {
     if cli.tmp {
         flags.insert(Flags::TMP | Flags::COPY);
     }
+    if CONFIG.edit.auto_copy {
+        flags.insert(Flags::COPY);
+    }
 
     debug!("profile name: {}", cli.profile_name);
 
@@ -52,14 +55,14 @@ pub fn start(cli: &crate::cli::CliEdit) {
         &cli.profile_name,
         ProfileFlags::LOOKUP_USER | ProfileFlags::DENY_BY_PATH | ProfileFlags::ASSUME_EXISTENCE,
     )
-    .unwrap()
+    .unwrap_or_else(|err| fatal!("{}", err))
     .into_pathbuf();
 
     let system_profile = Profile::new(
         &cli.profile_name,
         ProfileFlags::LOOKUP_SYSTEM | ProfileFlags::DENY_BY_PATH | ProfileFlags::ASSUME_EXISTENCE,
     )
-    .unwrap()
+    .unwrap_or_else(|err| fatal!("{}", err))
     .into_pathbuf();
 
     if flags.contains(Flags::TMP) {
@@ -143,28 +146,46 @@ fn prepare_edit(user_profile: &Path, system_profile: &Path, flags: Flags) {
         }
     }
 
+    if !system_profile.exists() && !user_profile.exists() {
+        create_from_template(user_profile);
+    }
+
     open_user_profile(user_profile);
 }
 
+/// Start new .profile files with the template of `fjp new` instead of an empty file
+fn create_from_template(user_profile: &Path) {
+    let name = match user_profile
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".profile"))
+    {
+        Some(name) => name,
+        None => return,
+    };
+
+    debug!("Create '{}' from the template.", user_profile.display());
+    if let Some(user_dir) = user_profile.parent() {
+        create_dir_all(user_dir)
+            .unwrap_or_else(|err| fatal!("Failed to create '{}': {}", user_dir.display(), err));
+    }
+    write(user_profile, template(name, &Template::default()))
+        .unwrap_or_else(|err| fatal!("Failed to create '{}': {}", user_profile.display(), err));
+}
+
 fn open_user_profile(profile: &Path) {
-    let editor = var_os("EDITOR").unwrap_or_else(|| {
-        warn!("$EDITOR not set or empty, using \"vim\" as fallback.");
-        OsString::from("vim")
-    });
-
-    debug!(
-        "Open '{}' with {}.",
-        profile.display(),
-        editor.to_string_lossy()
-    );
-    let exit_code = Command::new(&editor)
+    let editor = CONFIG.editor();
+
+    debug!("Open '{}' with {}.", profile.display(), editor[0]);
+    let exit_code = Command::new(&editor[0])
+        .args(&editor[1..])
         .arg(profile)
         .status()
-        .unwrap_or_else(|err| fatal!("Failed to start {}: {}", editor.to_string_lossy(), err));
+        .unwrap_or_else(|err| fatal!("Failed to start {}: {}", editor[0], err));
     if !exit_code.success() {
         warn!(
             "{} exited with exit code {}",
-            editor.to_string_lossy(),
+            editor[0],
             exit_code
                 .code()
                 .map_or("unknow".to_string(), |c| c.to_string())
@@ -0,0 +1,217 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for resolving the effective profile, i.e. a profile with all its includes
+
+use crate::conditions::Evaluator;
+use crate::profile::{Error as ProfileError, Profile, ProfileFlags};
+use crate::profile_stream::{Command, Content, Line, ProfileStream};
+use anyhow::{anyhow, ensure};
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The maximal depth of nested includes
+const MAX_INCLUDE_DEPTH: u8 = 16;
+
+/// The locations searched for a profile and its includes
+///
+/// firejail does not search the CWD and does not know the additional profile dirs of fjp.
+pub const LOOKUP_FLAGS: ProfileFlags = ProfileFlags::from_bits_truncate(
+    ProfileFlags::LOOKUP_USER.bits()
+        | ProfileFlags::LOOKUP_SYSTEM.bits()
+        | ProfileFlags::READ.bits(),
+);
+
+/// A line of an effective profile together with the file it comes from
+#[derive(Clone, Debug)]
+pub struct EffectiveLine {
+    pub path: Arc<PathBuf>,
+    pub line: Line,
+    /// The index of the `ignore` line which suppresses this line
+    pub ignored_by: Option<usize>,
+    /// The include depth of `path`, 0 for the profile itself
+    pub depth: u8,
+}
+impl EffectiveLine {
+    /// The command of this line, including the command of a conditional
+    pub fn command(&self) -> Option<&Command> {
+        match &*self.line.content {
+            Content::Command(command) => Some(command),
+            Content::Conditional(conditional) => Some(conditional.command()),
+            _ => None,
+        }
+    }
+
+    /// `path:lineno` of this line
+    pub fn location(&self) -> String {
+        match self.line.lineno {
+            Some(lineno) => format!("{}:{}", self.path.display(), lineno + 1),
+            None => self.path.display().to_string(),
+        }
+    }
+}
+
+/// Read the profile `name` and all files included by it
+///
+/// The lines are returned in the order firejail sees them, the `include` lines are kept.
+/// Includes which do not exist (e.g. most `.local`s) are skipped. Lines suppressed by an
+/// earlier `ignore` are kept too but have `ignored_by` set, ignored includes are not read.
+/// Conditional `ignore` and `include` lines only take effect if `evaluator` says their
+/// condition is true.
+pub fn effective_profile(name: &str, evaluator: &Evaluator) -> anyhow::Result<Vec<EffectiveLine>> {
+    let profile = Profile::new(name, LOOKUP_FLAGS)
+        .map_err(|err| anyhow!("Failed to read {}: {}", name, err))?;
+    resolve(
+        profile.path().map_or_else(PathBuf::new, Path::to_path_buf),
+        profile.raw_data(),
+        evaluator,
+        &load_include,
+    )
+}
+
+/// Like [`effective_profile`] for the file at `path`, but includes are searched in `dir`
+/// first, e.g. the directory of a repository of profiles
+pub fn effective_profile_in(
+    path: &Path,
+    dir: &Path,
+    evaluator: &Evaluator,
+) -> anyhow::Result<Vec<EffectiveLine>> {
+    let data = read_to_string(path)
+        .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?;
+    resolve(path.to_path_buf(), &data, evaluator, &|name| {
+        let file = dir.join(name);
+        if !name.contains('/') && file.is_file() {
+            let data = read_to_string(&file)
+                .map_err(|err| anyhow!("Failed to read '{}': {}", file.display(), err))?;
+            return Ok(Some((file, data)));
+        }
+        load_include(name)
+    })
+}
+
+/// Like [`effective_profile`] for the profile at `path` with the content `data`
+///
+/// `load` returns the path and content of an included file or `None` if it does not exist.
+fn resolve<F>(
+    path: PathBuf,
+    data: &str,
+    evaluator: &Evaluator,
+    load: &F,
+) -> anyhow::Result<Vec<EffectiveLine>>
+where
+    F: Fn(&str) -> anyhow::Result<Option<(PathBuf, String)>>,
+{
+    let mut lines = Vec::new();
+    process(Arc::new(path), data, &mut lines, 0, evaluator, load)?;
+    Ok(lines)
+}
+
+/// Read the include `name` from the locations searched by firejail
+fn load_include(name: &str) -> anyhow::Result<Option<(PathBuf, String)>> {
+    match Profile::new(name, LOOKUP_FLAGS) {
+        Ok(included) => {
+            let data = included.raw_data().to_string();
+            Ok(Some((included.into_pathbuf(), data)))
+        }
+        Err(ProfileError::ReadError { source, .. })
+            if matches!(
+                source.downcast_ref::<ProfileError>(),
+                Some(ProfileError::NoPath)
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(anyhow!("Failed to read '{}': {}", name, err)),
+    }
+}
+
+/// The effective profile of `data` with the includes from `files`, for tests
+#[cfg(test)]
+pub fn effective_profile_from(
+    data: &str,
+    files: &[(&str, &str)],
+    evaluator: &Evaluator,
+) -> Vec<EffectiveLine> {
+    resolve(PathBuf::from("test.profile"), data, evaluator, &|name| {
+        Ok(files
+            .iter()
+            .find(|(file, _)| *file == name)
+            .map(|(file, data)| (PathBuf::from(file), data.to_string())))
+    })
+    .unwrap()
+}
+
+fn process<F>(
+    path: Arc<PathBuf>,
+    data: &str,
+    lines: &mut Vec<EffectiveLine>,
+    depth: u8,
+    evaluator: &Evaluator,
+    load: &F,
+) -> anyhow::Result<()>
+where
+    F: Fn(&str) -> anyhow::Result<Option<(PathBuf, String)>>,
+{
+    let profile_stream = data
+        .parse::<ProfileStream>()
+        .unwrap_or_else(|profile_stream| profile_stream);
+
+    for line in profile_stream {
+        let mut effective_line = EffectiveLine {
+            path: Arc::clone(&path),
+            line,
+            ignored_by: None,
+            depth,
+        };
+        effective_line.ignored_by = match effective_line.command() {
+            Some(Command::Ignore(_)) | None => None,
+            Some(command) => lines.iter().position(|line| {
+                matches!(line.command(), Some(Command::Ignore(pattern)) if pattern.matches(command))
+                    && evaluator.applies(&line.line.content)
+            }),
+        };
+        let include = match effective_line.command() {
+            Some(Command::Include(include))
+                if effective_line.ignored_by.is_none()
+                    && evaluator.applies(&effective_line.line.content) =>
+            {
+                Some(include.clone())
+            }
+            _ => None,
+        };
+        lines.push(effective_line);
+
+        if let Some(include) = include {
+            ensure!(depth < MAX_INCLUDE_DEPTH, "To many include levels");
+            if let Some((included_path, included_data)) = load(&include)? {
+                process(
+                    Arc::new(included_path),
+                    &included_data,
+                    lines,
+                    depth + 1,
+                    evaluator,
+                    load,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
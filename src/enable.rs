@@ -18,29 +18,49 @@
  */
 
 use crate::{
-    disable::DISABLED_DIR,
+    disable::{shadow_content, DISABLED_DIR, GLOBALS_STATE_FILE, LOCALS_STATE_FILE},
+    fatal,
+    location::Location,
     profile::{Profile, ProfileFlags},
-    utils::input,
+    utils::{input, join},
     USER_PROFILE_DIR,
 };
 use log::{debug, error, info, warn};
-use std::fs::rename;
+use std::fs::{read_link, read_to_string, remove_file, rename, symlink_metadata, write};
+use std::path::Path;
 
 pub fn start(cli: &crate::cli::CliEnable) {
     debug!("subcommand: enable");
 
     if cli.user {
         enable_user();
+    } else if cli.locals {
+        enable_locals(LOCALS_STATE_FILE, &USER_PROFILE_DIR, &DISABLED_DIR);
+    } else if cli.globals {
+        enable_locals(GLOBALS_STATE_FILE, &USER_PROFILE_DIR, &DISABLED_DIR);
     } else {
-        let profile = Profile::new(
-            cli.profile_name.as_deref().unwrap(),
-            ProfileFlags::LOOKUP_USER | ProfileFlags::ASSUME_EXISTENCE | ProfileFlags::DENY_BY_PATH,
-        )
-        .unwrap();
-        enable_profile(&profile);
+        enable(cli.profile_name.as_deref().unwrap(), true);
     }
 }
 
+/// Enable the disabled profile `name`
+///
+/// If `interactive` is false, existing files are never overridden or removed instead of asking.
+pub fn enable(name: &str, interactive: bool) {
+    let profile = Profile::new(
+        name,
+        ProfileFlags::LOOKUP_USER | ProfileFlags::ASSUME_EXISTENCE | ProfileFlags::DENY_BY_PATH,
+    )
+    .unwrap_or_else(|err| fatal!("{}", err));
+    // NOTE: unwrap can't fail because profile is created with ASSUME_EXISTENCE.
+    enable_profile(
+        profile.full_name(),
+        profile.path().unwrap(),
+        &DISABLED_DIR,
+        interactive,
+    );
+}
+
 fn enable_user() {
     let mut disabled_user_profile_dir = USER_PROFILE_DIR.to_owned_inner();
     disabled_user_profile_dir.set_extension("disabled");
@@ -52,20 +72,31 @@ fn enable_user() {
         .unwrap_or_else(|err| error!("Rename failed: {}", err));
 }
 
-fn enable_profile(profile: &Profile<'_>) {
-    let disabled_profile = DISABLED_DIR.get_profile_path(profile.full_name());
+/// Enable the profile `full_name` from `disabled_dir` at `enabled_profile`
+fn enable_profile(
+    full_name: &str,
+    enabled_profile: &Path,
+    disabled_dir: &Location,
+    interactive: bool,
+) {
+    let disabled_profile = disabled_dir.get_profile_path(full_name);
 
-    if !disabled_profile.exists() {
-        error!("{} is not disabled.", profile.full_name());
+    let is_system_profile = match symlink_metadata(&disabled_profile) {
+        Ok(metadata) => metadata.file_type().is_symlink(),
+        Err(_) => {
+            error!("{} is not disabled.", full_name);
+            return;
+        }
+    };
+
+    if is_system_profile {
+        enable_system_profile(full_name, &disabled_profile, enabled_profile, interactive);
         return;
     }
 
-    // NOTE: unwrap can't fail because profile is created with ASSUME_EXISTENCE.
-    let enabled_profile = profile.path().unwrap();
-
     if enabled_profile.exists() {
-        warn!("Profile '{}' is alread enabled.", profile.full_name());
-        if input("Override? [Y/n] ").unwrap() != "y" {
+        warn!("Profile '{}' is alread enabled.", full_name);
+        if !interactive || input("Override? [Y/n] ").unwrap() != "y" {
             info!("Skipping");
             return;
         }
@@ -79,3 +110,261 @@ fn enable_profile(profile: &Profile<'_>) {
     rename(&disabled_profile, enabled_profile)
         .unwrap_or_else(|err| error!("Rename failed: {}", err));
 }
+
+/// Enable a profile from `/etc/firejail` or an additional profile dir which was disabled
+/// by a generated user profile
+fn enable_system_profile(
+    full_name: &str,
+    disabled_profile: &Path,
+    shadow_profile: &Path,
+    interactive: bool,
+) {
+    let remove_shadow_profile = match read_to_string(shadow_profile) {
+        Ok(content)
+            if read_link(disabled_profile).map_or(false, |shadowed| {
+                content == shadow_content(full_name, &shadowed)
+            }) =>
+        {
+            true
+        }
+        Ok(_) => {
+            warn!(
+                "'{}' has been modified since it was disabled.",
+                shadow_profile.display()
+            );
+            if !interactive || input("Remove it anyway? [y/N] ").unwrap() != "y" {
+                info!("Skipping");
+                return;
+            }
+            true
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            info!(
+                "'{}' does not exist anymore, only removing the marker.",
+                shadow_profile.display()
+            );
+            false
+        }
+        Err(err) => {
+            error!("Failed to read '{}': {}", shadow_profile.display(), err);
+            return;
+        }
+    };
+
+    if remove_shadow_profile {
+        debug!("Remove '{}'", shadow_profile.display());
+        if let Err(err) = remove_file(shadow_profile) {
+            error!("Failed to remove '{}': {}", shadow_profile.display(), err);
+            return;
+        }
+    }
+
+    debug!("Remove '{}'", disabled_profile.display());
+    remove_file(disabled_profile)
+        .unwrap_or_else(|err| error!("Failed to remove the marker for {}: {}", full_name, err));
+}
+
+/// Move all `.local`s remembered in `state_file` back from `disabled_dir` to `user_dir`
+///
+/// `.local`s which can not be enabled are kept in `state_file`, so a later call can retry.
+fn enable_locals(state_file: &str, user_dir: &Location, disabled_dir: &Location) {
+    let state_file = disabled_dir.get_profile_path(state_file);
+    let disabled_locals = match read_to_string(&state_file) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            error!("There are no disabled .local files to enable.");
+            return;
+        }
+        Err(err) => {
+            error!("Failed to read '{}': {}", state_file.display(), err);
+            return;
+        }
+    };
+
+    let mut remaining_locals = Vec::new();
+    for name in disabled_locals.lines().filter(|name| !name.is_empty()) {
+        let disabled_local = disabled_dir.get_profile_path(name);
+        if !disabled_local.exists() {
+            debug!("'{}' has already been enabled.", name);
+            continue;
+        }
+
+        let enabled_local = user_dir.get_profile_path(name);
+        if enabled_local.exists() {
+            warn!("'{}' exists already, keeping it disabled.", name);
+            remaining_locals.push(name);
+            continue;
+        }
+
+        debug!(
+            "Move '{}' to '{}'",
+            disabled_local.display(),
+            enabled_local.display()
+        );
+        if let Err(err) = rename(&disabled_local, &enabled_local) {
+            error!("Failed to enable '{}': {}", name, err);
+            remaining_locals.push(name);
+        }
+    }
+
+    if remaining_locals.is_empty() {
+        remove_file(&state_file)
+            .unwrap_or_else(|err| error!("Failed to remove '{}': {}", state_file.display(), err));
+    } else {
+        write(&state_file, join('\n', &remaining_locals) + "\n")
+            .unwrap_or_else(|err| error!("Failed to write '{}': {}", state_file.display(), err));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disable::disable_locals;
+    use crate::profile::GLOBALS;
+    use crate::utils::TempDir;
+    use std::fs::{create_dir_all, read_dir};
+    use std::os::unix::fs::symlink;
+
+    /// Disable the system profile `full_name` like `fjp disable` and return the shadow
+    /// profile in the user dir, the disabled dir and the system profile
+    fn shadowed(
+        tmp: &TempDir,
+        full_name: &str,
+    ) -> (std::path::PathBuf, Location, std::path::PathBuf) {
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        create_dir_all(&disabled_dir).unwrap();
+        let system_profile = tmp.write(&format!("system/{}", full_name), "noroot\n");
+        let shadow_profile = tmp.write(
+            &format!("user/{}", full_name),
+            &shadow_content(full_name, &system_profile),
+        );
+        symlink(&system_profile, disabled_dir.get_profile_path(full_name)).unwrap();
+        (shadow_profile, disabled_dir, system_profile)
+    }
+
+    #[test]
+    fn enable_system_profile_removes_the_shadow() {
+        let tmp = TempDir::new();
+        let (shadow_profile, disabled_dir, system_profile) = shadowed(&tmp, "foo.profile");
+
+        enable_profile("foo.profile", &shadow_profile, &disabled_dir, false);
+        assert!(!shadow_profile.exists());
+        assert!(symlink_metadata(disabled_dir.get_profile_path("foo.profile")).is_err());
+        assert_eq!(read_to_string(&system_profile).unwrap(), "noroot\n");
+    }
+
+    #[test]
+    fn enable_system_profile_keeps_a_modified_shadow() {
+        let tmp = TempDir::new();
+        let (shadow_profile, disabled_dir, _) = shadowed(&tmp, "foo.profile");
+        write(&shadow_profile, "quiet\n").unwrap();
+
+        enable_profile("foo.profile", &shadow_profile, &disabled_dir, false);
+        assert_eq!(read_to_string(&shadow_profile).unwrap(), "quiet\n");
+        assert!(symlink_metadata(disabled_dir.get_profile_path("foo.profile")).is_ok());
+    }
+
+    #[test]
+    fn enable_system_profile_without_shadow_removes_the_marker() {
+        let tmp = TempDir::new();
+        let (shadow_profile, disabled_dir, _) = shadowed(&tmp, "foo.inc");
+        remove_file(&shadow_profile).unwrap();
+
+        enable_profile("foo.inc", &shadow_profile, &disabled_dir, false);
+        assert!(symlink_metadata(disabled_dir.get_profile_path("foo.inc")).is_err());
+    }
+
+    #[test]
+    fn enable_user_profile_moves_it_back() {
+        let tmp = TempDir::new();
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        tmp.write("user/disabled/foo.profile", "noroot\n");
+        let user_profile = tmp.path().join("user/foo.profile");
+
+        enable_profile("foo.profile", &user_profile, &disabled_dir, false);
+        assert_eq!(read_to_string(&user_profile).unwrap(), "noroot\n");
+        assert!(!disabled_dir.get_profile_path("foo.profile").exists());
+
+        // Never override an enabled profile without asking
+        tmp.write("user/disabled/foo.profile", "quiet\n");
+        enable_profile("foo.profile", &user_profile, &disabled_dir, false);
+        assert_eq!(read_to_string(&user_profile).unwrap(), "noroot\n");
+        assert!(disabled_dir.get_profile_path("foo.profile").exists());
+    }
+
+    #[test]
+    fn enable_locals_restores_the_disabled_set() {
+        let tmp = TempDir::new();
+        let user_dir = Location::from(tmp.path().join("user"));
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        create_dir_all(&disabled_dir).unwrap();
+        tmp.write("user/foo.local", "noroot\n");
+        tmp.write("user/globals.local", "nosound\n");
+        tmp.write("user/foo.profile", "include foo.local\n");
+        // Already disabled by hand, not remembered in the state file
+        tmp.write("user/disabled/bar.local", "quiet\n");
+        tmp.write("user/bar.local", "net none\n");
+        let names = |location: &Location| {
+            let mut names = read_dir(location)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| name.ends_with(".local"))
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names
+        };
+
+        disable_locals(
+            LOCALS_STATE_FILE,
+            |name| name.ends_with(".local"),
+            &user_dir,
+            &disabled_dir,
+        );
+        assert_eq!(
+            read_to_string(disabled_dir.get_profile_path(LOCALS_STATE_FILE)).unwrap(),
+            "foo.local\nglobals.local\n"
+        );
+        assert_eq!(names(&user_dir), ["bar.local"]);
+
+        tmp.write("user/new.local", "nodvd\n");
+        enable_locals(LOCALS_STATE_FILE, &user_dir, &disabled_dir);
+        assert_eq!(
+            names(&user_dir),
+            ["bar.local", "foo.local", "globals.local", "new.local"]
+        );
+        assert_eq!(names(&disabled_dir), ["bar.local"]);
+        assert!(!disabled_dir.get_profile_path(LOCALS_STATE_FILE).exists());
+        assert_eq!(
+            read_to_string(user_dir.get_profile_path("new.local")).unwrap(),
+            "nodvd\n"
+        );
+    }
+
+    #[test]
+    fn enable_globals_keeps_conflicting_locals_disabled() {
+        let tmp = TempDir::new();
+        let user_dir = Location::from(tmp.path().join("user"));
+        let disabled_dir = Location::from(tmp.path().join("user/disabled"));
+        create_dir_all(&disabled_dir).unwrap();
+        tmp.write("user/globals.local", "nosound\n");
+        tmp.write("user/foo.local", "noroot\n");
+
+        disable_locals(
+            GLOBALS_STATE_FILE,
+            |name| GLOBALS.contains(&name),
+            &user_dir,
+            &disabled_dir,
+        );
+        assert!(user_dir.get_profile_path("foo.local").exists());
+        tmp.write("user/globals.local", "novideo\n");
+        enable_locals(GLOBALS_STATE_FILE, &user_dir, &disabled_dir);
+        assert_eq!(
+            read_to_string(user_dir.get_profile_path("globals.local")).unwrap(),
+            "novideo\n"
+        );
+        assert_eq!(
+            read_to_string(disabled_dir.get_profile_path(GLOBALS_STATE_FILE)).unwrap(),
+            "globals.local\n"
+        );
+    }
+}
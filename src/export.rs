@@ -0,0 +1,80 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::cli::CliExportFormat;
+use crate::fatal;
+use crate::profile::{Profile, ProfileFlags};
+use crate::profile_stream::ProfileStream;
+use log::{debug, warn};
+use std::fs::File;
+use std::io::{stdout, Write};
+
+pub fn start(cli: &crate::cli::CliExport) {
+    debug!("subcommand: export");
+
+    let profile = Profile::new(
+        &cli.profile_name,
+        ProfileFlags::default().with(ProfileFlags::READ),
+    )
+    .unwrap_or_else(|err| fatal!("Failed to read {}: {}", cli.profile_name, err));
+
+    let profile_stream =
+        profile
+            .raw_data()
+            .parse::<ProfileStream>()
+            .unwrap_or_else(|profile_stream| {
+                warn!(
+                    "{} contains invalid lines, they are exported as type 'invalid'.",
+                    profile.full_name()
+                );
+                profile_stream
+            });
+
+    let exported = serialize(&profile_stream, cli.to)
+        .unwrap_or_else(|err| fatal!("Failed to export {}: {}", profile.full_name(), err));
+
+    if let Some(output_file) = &cli.output_file {
+        File::create(output_file)
+            .and_then(|mut file| file.write_all(exported.as_bytes()))
+            .unwrap_or_else(|err| fatal!("Failed to write '{}': {}", output_file, err));
+    } else {
+        stdout().write_all(exported.as_bytes()).unwrap();
+    }
+}
+
+/// Serialize `profile_stream` into `format`
+pub fn serialize(
+    profile_stream: &ProfileStream,
+    format: CliExportFormat,
+) -> anyhow::Result<String> {
+    Ok(match format {
+        CliExportFormat::Json => serde_json::to_string_pretty(profile_stream)? + "\n",
+        CliExportFormat::Yaml => serde_yaml::to_string(profile_stream)?,
+        CliExportFormat::Toml => toml::to_string_pretty(profile_stream)?,
+    })
+}
+
+/// Deserialize a [`ProfileStream`] from `data` in `format`
+pub fn deserialize(data: &str, format: CliExportFormat) -> anyhow::Result<ProfileStream> {
+    Ok(match format {
+        CliExportFormat::Json => serde_json::from_str(data)?,
+        CliExportFormat::Yaml => serde_yaml::from_str(data)?,
+        CliExportFormat::Toml => toml::from_str(data)?,
+    })
+}
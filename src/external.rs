@@ -0,0 +1,72 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for running external subcommands (`fjp-<name>` executables in `PATH`)
+
+use crate::cli::CliFormat;
+use crate::fatal;
+use crate::location::Location;
+use crate::{EXTRA_PROFILE_DIRS, SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
+use log::debug;
+use std::env::join_paths;
+use std::io::ErrorKind;
+use std::process::{exit, Command};
+
+/// Run `fjp-<args[0]>` with the remaining `args` and exit with its exit code
+///
+/// The external subcommand gets the following environment variables:
+///
+/// - `FJP_SYSTEM_PROFILE_DIR`: the system profile dir (`/etc/firejail`)
+/// - `FJP_USER_PROFILE_DIR`: the user profile dir (`~/.config/firejail`)
+/// - `FJP_PROFILE_PATH`: the additional profile dirs, separated by `:`
+/// - `FJP_FORMAT`: the value of `--output`
+pub fn start(args: &[String], format: CliFormat) {
+    let program = format!("fjp-{}", args[0]);
+    debug!("external subcommand: {}", program);
+
+    let status = Command::new(&program)
+        .args(&args[1..])
+        .env("FJP_SYSTEM_PROFILE_DIR", SYSTEM_PROFILE_DIR.get_ref())
+        .env("FJP_USER_PROFILE_DIR", USER_PROFILE_DIR.get_ref())
+        .env(
+            "FJP_PROFILE_PATH",
+            join_paths(EXTRA_PROFILE_DIRS.iter().map(Location::get_ref)).unwrap_or_default(),
+        )
+        .env(
+            "FJP_FORMAT",
+            match format {
+                CliFormat::Text => "text",
+                CliFormat::Json => "json",
+            },
+        )
+        .status()
+        .unwrap_or_else(|err| {
+            if err.kind() == ErrorKind::NotFound {
+                fatal!(
+                    "'{}' is neither a fjp subcommand nor an alias and no '{}' was found in PATH.",
+                    args[0],
+                    program
+                );
+            } else {
+                fatal!("Failed to start {}: {}", program, err);
+            }
+        });
+
+    exit(status.code().unwrap_or(1));
+}
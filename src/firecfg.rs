@@ -0,0 +1,324 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for inspecting the programs sandboxed by firecfg
+//!
+//! firecfg creates symlinks to firejail named like the programs in `/usr/local/bin`,
+//! which comes before `/usr/bin` in `PATH`. The programs are listed in `firecfg.config`
+//! and `firecfg.d/*.conf`.
+
+use crate::cli::{CliFirecfgStatus, CliFormat, FirecfgSubcommands};
+use crate::config::CONFIG;
+use crate::coverage::desktop_entry_exec;
+use crate::effective::LOOKUP_FLAGS;
+use crate::profile::{Profile, ProfileFlags};
+use crate::utils::{home_dir, print_json};
+use crate::which::{is_firejail_symlink, resolve_program};
+use crate::SYSTEM_PROFILE_DIR;
+use log::{debug, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::env::var_os;
+use std::fs::{read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+/// The default directory of the symlinks
+const DEFAULT_BIN_DIR: &str = "/usr/local/bin";
+
+/// The status of a program known to firecfg
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    /// Symlinked and has a profile
+    Sandboxed,
+    /// Installed and has a profile, but there is no symlink
+    NotLinked,
+    /// Symlinked, but there is no profile for it
+    NoProfile,
+    /// Symlinked, but the program is not installed
+    NotInstalled,
+}
+impl Status {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Sandboxed => "Sandboxed by default",
+            Self::NotLinked => "Profile but no symlink",
+            Self::NoProfile => "Symlink but no profile",
+            Self::NotInstalled => "Symlink but not installed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Program {
+    name: String,
+    status: Status,
+    program: Option<PathBuf>,
+    profile: Option<PathBuf>,
+}
+
+/// A desktop file which starts its program with firejail
+#[derive(Debug, Serialize)]
+struct DesktopOverride {
+    path: PathBuf,
+    exec: String,
+}
+
+pub fn start(cli: &crate::cli::CliFirecfg, format: CliFormat) {
+    debug!("subcommand: firecfg");
+
+    match &cli.subcommand {
+        FirecfgSubcommands::Status(cli) => status(cli, format),
+    }
+}
+
+fn status(cli: &CliFirecfgStatus, format: CliFormat) {
+    let bin_dir = cli
+        .bin_dir
+        .clone()
+        .or_else(|| CONFIG.firecfg_bin_dir())
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_BIN_DIR));
+
+    let listed = firecfg_programs(SYSTEM_PROFILE_DIR.get_ref());
+    let linked = firejail_symlinks(&bin_dir);
+
+    let mut programs = Vec::new();
+    for name in listed.union(&linked) {
+        let is_linked = linked.contains(name);
+        let (program, _) = resolve_program(name);
+        let profile = Profile::new(name, LOOKUP_FLAGS.without(ProfileFlags::READ))
+            .ok()
+            .and_then(|profile| profile.path().map(Path::to_path_buf));
+        let status = match (is_linked, &program, &profile) {
+            (true, None, _) => Status::NotInstalled,
+            (true, Some(_), None) => Status::NoProfile,
+            (true, Some(_), Some(_)) => Status::Sandboxed,
+            (false, Some(_), Some(_)) => Status::NotLinked,
+            // Not installed or no profile, firecfg would not create a symlink.
+            (false, _, _) => continue,
+        };
+        programs.push(Program {
+            name: name.clone(),
+            status,
+            program,
+            profile,
+        });
+    }
+    let desktop_overrides = applications_dir()
+        .map(|dir| desktop_overrides(&dir))
+        .unwrap_or_default();
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "bin_dir": bin_dir,
+            "programs": programs,
+            "desktop_overrides": desktop_overrides,
+        }));
+        return;
+    }
+
+    for status in [
+        Status::Sandboxed,
+        Status::NotLinked,
+        Status::NoProfile,
+        Status::NotInstalled,
+    ] {
+        let mut programs = programs
+            .iter()
+            .filter(|program| program.status == status)
+            .peekable();
+        if programs.peek().is_none() {
+            continue;
+        }
+        println!("{}:", status.description());
+        for program in programs {
+            println!("  {}", program.name);
+        }
+    }
+    if !desktop_overrides.is_empty() {
+        println!("Desktop files with Exec=firejail:");
+        for desktop_override in &desktop_overrides {
+            println!(
+                "  {}: {}",
+                desktop_override.path.display(),
+                desktop_override.exec
+            );
+        }
+    }
+}
+
+/// The programs from `firecfg.config` and `firecfg.d/*.conf` in `dir`
+///
+/// Lines starting with `!` in `firecfg.d` remove a program.
+fn firecfg_programs(dir: &Path) -> BTreeSet<String> {
+    let mut files = vec![dir.join("firecfg.config")];
+    if let Ok(entries) = read_dir(dir.join("firecfg.d")) {
+        let mut confs = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "conf"))
+            .collect::<Vec<_>>();
+        confs.sort();
+        files.extend(confs);
+    }
+
+    let mut programs = BTreeSet::new();
+    for file in files {
+        let data = match read_to_string(&file) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Failed to read {}: {}", file.display(), err);
+                continue;
+            }
+        };
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.strip_prefix('!') {
+                Some(removed) => programs.remove(removed),
+                None => programs.insert(line.to_string()),
+            };
+        }
+    }
+    programs
+}
+
+/// The names of all symlinks to firejail in `bin_dir`
+fn firejail_symlinks(bin_dir: &Path) -> BTreeSet<String> {
+    match read_dir(bin_dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|entry| is_firejail_symlink(&entry.path()))
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect(),
+        Err(err) => {
+            warn!("Failed to read {}: {}", bin_dir.display(), err);
+            BTreeSet::new()
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME/applications`, the desktop files of the user
+fn applications_dir() -> Option<PathBuf> {
+    let data_home = match var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir()?.join(".local/share"),
+    };
+    Some(data_home.join("applications"))
+}
+
+/// The desktop files in `dir` whose `[Desktop Entry]` starts the program with firejail
+fn desktop_overrides(dir: &Path) -> Vec<DesktopOverride> {
+    let mut paths = match read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "desktop"))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            debug!("Failed to read {}: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let data = read_to_string(&path).ok()?;
+            let exec = desktop_entry_exec(&data)?;
+            let sandboxed = exec.split_whitespace().next().map_or(false, |command| {
+                command.trim_matches('"').rsplit('/').next() == Some("firejail")
+            });
+            sandboxed.then(|| DesktopOverride {
+                path,
+                exec: exec.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn test_firecfg_programs() {
+        let tmp = TempDir::new();
+        tmp.write("firecfg.config", "# comment\nfoo\nbar\n\nbaz\n");
+        tmp.write("firecfg.d/20-b.conf", "qux\n!baz\n");
+        tmp.write("firecfg.d/10-a.conf", "!foo\n!qux\n");
+        tmp.write("firecfg.d/30-c.txt", "!bar\n");
+
+        assert_eq!(
+            firecfg_programs(tmp.path()).into_iter().collect::<Vec<_>>(),
+            ["bar", "qux"]
+        );
+    }
+
+    #[test]
+    fn test_firecfg_programs_without_firecfg_d() {
+        let tmp = TempDir::new();
+        tmp.write("firecfg.config", "foo\n");
+        assert_eq!(
+            firecfg_programs(tmp.path()).into_iter().collect::<Vec<_>>(),
+            ["foo"]
+        );
+    }
+
+    #[test]
+    fn test_desktop_overrides() {
+        let tmp = TempDir::new();
+        tmp.write(
+            "b.desktop",
+            "[Desktop Entry]\nExec=/usr/bin/firejail --net=none bar %u\n",
+        );
+        tmp.write("a.desktop", "[Desktop Entry]\nExec=firejail foo\n");
+        tmp.write("c.desktop", "[Desktop Entry]\nExec=foo --firejail\n");
+        tmp.write(
+            "e.desktop",
+            "[Desktop Entry]\nExec=foo %u\n\n[Desktop Action private]\nExec=firejail foo\n",
+        );
+        tmp.write(
+            "f.desktop",
+            "[Desktop Action private]\nExec=foo\n[Desktop Entry]\nExec=firejail foo\n",
+        );
+        tmp.write("d.txt", "Exec=firejail foo\n");
+
+        let overrides = desktop_overrides(tmp.path())
+            .into_iter()
+            .map(|desktop_override| (desktop_override.path, desktop_override.exec))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            overrides,
+            [
+                (tmp.path().join("a.desktop"), "firejail foo".to_string()),
+                (
+                    tmp.path().join("b.desktop"),
+                    "/usr/bin/firejail --net=none bar %u".to_string()
+                ),
+                (tmp.path().join("f.desktop"), "firejail foo".to_string()),
+            ]
+        );
+        assert!(desktop_overrides(&tmp.path().join("missing")).is_empty());
+    }
+}
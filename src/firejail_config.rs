@@ -0,0 +1,276 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for firejail's global configuration file `firejail.config`
+
+use crate::profile_stream::{Command, DBusPolicy, SeccompErrorAction};
+use crate::SYSTEM_PROFILE_DIR;
+use log::{debug, warn};
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+macro_rules! firejail_config {
+    ( $( $field:ident = $name:literal, default: $default:literal; )* ) => {
+        /// The settings from `firejail.config`
+        ///
+        /// Settings which are not in the file have firejail's default value.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct FirejailConfig {
+            $(
+                #[doc = concat!("`", $name, "`, default: ", stringify!($default))]
+                pub $field: bool,
+            )*
+            /// `seccomp-error-action`, `None` is firejail's default (EPERM)
+            pub seccomp_error_action: Option<SeccompErrorAction>,
+        }
+        impl Default for FirejailConfig {
+            fn default() -> Self {
+                Self {
+                    $( $field: $default, )*
+                    seccomp_error_action: None,
+                }
+            }
+        }
+        impl FirejailConfig {
+            /// All features (yes/no settings) as `(name, value, default)`
+            pub fn features(&self) -> Vec<(&'static str, bool, bool)> {
+                vec![ $( ($name, self.$field, $default), )* ]
+            }
+
+            fn feature_mut(&mut self, name: &str) -> Option<&mut bool> {
+                match name {
+                    $( $name => Some(&mut self.$field), )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+// Creates `struct FirejailConfig`
+firejail_config! {
+    allow_tray = "allow-tray", default: false;
+    apparmor = "apparmor", default: true;
+    bind = "bind", default: true;
+    browser_allow_drm = "browser-allow-drm", default: false;
+    browser_disable_u2f = "browser-disable-u2f", default: true;
+    cgroup = "cgroup", default: true;
+    chroot = "chroot", default: false;
+    dbus = "dbus", default: true;
+    disable_mnt = "disable-mnt", default: false;
+    file_transfer = "file-transfer", default: true;
+    force_nonewprivs = "force-nonewprivs", default: false;
+    join = "join", default: true;
+    network = "network", default: true;
+    overlayfs = "overlayfs", default: false;
+    private_bin = "private-bin", default: true;
+    private_cache = "private-cache", default: true;
+    private_etc = "private-etc", default: true;
+    private_home = "private-home", default: true;
+    private_lib = "private-lib", default: true;
+    private_opt = "private-opt", default: true;
+    private_srv = "private-srv", default: true;
+    quiet_by_default = "quiet-by-default", default: false;
+    restricted_network = "restricted-network", default: false;
+    seccomp = "seccomp", default: true;
+    tmpfs = "tmpfs", default: true;
+    tracelog = "tracelog", default: true;
+    userns = "userns", default: true;
+    whitelist = "whitelist", default: true;
+    x11 = "x11", default: true;
+}
+
+impl FirejailConfig {
+    /// The path of `firejail.config`, next to the system profiles
+    ///
+    /// firejail has no per-user configuration file, only this one is read.
+    pub fn path() -> PathBuf {
+        SYSTEM_PROFILE_DIR.get_ref().join("firejail.config")
+    }
+
+    /// Read `firejail.config`, a missing or unreadable file results in the defaults
+    pub fn load() -> Self {
+        let path = Self::path();
+        match read_to_string(&path) {
+            Ok(data) => Self::parse(&data),
+            Err(err) => {
+                if err.kind() == ErrorKind::NotFound {
+                    debug!("{} does not exist, using the defaults.", path.display());
+                } else {
+                    warn!("Failed to read {}: {}", path.display(), err);
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Parse the content of a `firejail.config`
+    ///
+    /// Every setting is a line with `name value`, `#` starts a comment.
+    /// Unknown settings are ignored, invalid values are reported and ignored.
+    pub fn parse(data: &str) -> Self {
+        let mut config = Self::default();
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            if name == "seccomp-error-action" {
+                match value.parse() {
+                    Ok(action) => config.seccomp_error_action = Some(action),
+                    Err(_) => warn!("Invalid value for {} in firejail.config: '{}'", name, value),
+                }
+                continue;
+            }
+            let feature = match config.feature_mut(name) {
+                Some(feature) => feature,
+                None => {
+                    debug!("Ignoring '{}' from firejail.config.", name);
+                    continue;
+                }
+            };
+            match value {
+                "yes" => *feature = true,
+                "no" => *feature = false,
+                _ => warn!("Invalid value for {} in firejail.config: '{}'", name, value),
+            }
+        }
+        config
+    }
+
+    /// The setting of this config which disables `command` and its value
+    ///
+    /// `restricted-network yes` disables `netfilter` for regular users, for the other
+    /// commands it is a feature set to `no`.
+    pub fn disabled_feature_for(&self, command: &Command) -> Option<(&'static str, bool)> {
+        use Command::*;
+
+        if *command == Netfilter && self.network && self.restricted_network {
+            return Some(("restricted-network", true));
+        }
+        let (feature, enabled) = match command {
+            Apparmor => ("apparmor", self.apparmor),
+            Bind(..) => ("bind", self.bind),
+            DBusUser(DBusPolicy::Filter)
+            | DBusSystem(DBusPolicy::Filter)
+            | DBusUserOwn(_)
+            | DBusUserTalk(_)
+            | DBusSystemOwn(_)
+            | DBusSystemTalk(_) => ("dbus", self.dbus),
+            JoinOrStart(_) => ("join", self.join),
+            NetNone | Netfilter => ("network", self.network),
+            Noroot => ("userns", self.userns),
+            Overlay | OverlayNamed(_) | OverlayTmpfs => ("overlayfs", self.overlayfs),
+            PrivateBin(_) => ("private-bin", self.private_bin),
+            PrivateCache => ("private-cache", self.private_cache),
+            PrivateEtc(_) => ("private-etc", self.private_etc),
+            PrivateHome(_) => ("private-home", self.private_home),
+            PrivateLib(_) => ("private-lib", self.private_lib),
+            PrivateOpt(_) => ("private-opt", self.private_opt),
+            PrivateSrv(_) => ("private-srv", self.private_srv),
+            Seccomp(_) | SeccompBlockSecondary | SeccompDrop(_) => ("seccomp", self.seccomp),
+            Tmpfs(_) => ("tmpfs", self.tmpfs),
+            Tracelog => ("tracelog", self.tracelog),
+            Whitelist(_) | Nowhitelist(_) => ("whitelist", self.whitelist),
+            X11(_) => ("x11", self.x11),
+            _ => return None,
+        };
+        if enabled {
+            None
+        } else {
+            Some((feature, false))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config = FirejailConfig::parse(
+            "# browser-allow-drm yes\nbrowser-disable-u2f no\nallow-tray yes\nuserns  no\nseccomp-error-action kill\nxvfb-screen 800x600x24\n",
+        );
+        assert_eq!(
+            config,
+            FirejailConfig {
+                allow_tray: true,
+                browser_disable_u2f: false,
+                userns: false,
+                seccomp_error_action: Some(SeccompErrorAction::Kill),
+                ..FirejailConfig::default()
+            }
+        );
+        assert_eq!(
+            config.disabled_feature_for(&Command::Noroot),
+            Some(("userns", false))
+        );
+        assert_eq!(config.disabled_feature_for(&Command::Seccomp(None)), None);
+    }
+
+    /// The setting disabling `line` with `config`
+    fn disabled(config: &str, line: &str) -> Option<(&'static str, bool)> {
+        FirejailConfig::parse(config).disabled_feature_for(&line.parse().unwrap())
+    }
+
+    #[test]
+    fn disabled_x11() {
+        assert_eq!(disabled("", "x11 xpra"), None);
+        assert_eq!(disabled("x11 no\n", "x11"), Some(("x11", false)));
+        assert_eq!(disabled("x11 no\n", "x11 xvfb"), Some(("x11", false)));
+        assert_eq!(disabled("x11 no\n", "x11 none"), None);
+    }
+
+    #[test]
+    fn disabled_private_home() {
+        assert_eq!(disabled("", "private-home .mozilla"), None);
+        assert_eq!(
+            disabled("private-home no\n", "private-home .mozilla"),
+            Some(("private-home", false))
+        );
+    }
+
+    #[test]
+    fn disabled_overlayfs() {
+        // overlayfs defaults to no.
+        for line in ["overlay", "overlay-named foo", "overlay-tmpfs"] {
+            assert_eq!(disabled("", line), Some(("overlayfs", false)));
+            assert_eq!(disabled("overlayfs yes\n", line), None);
+        }
+    }
+
+    #[test]
+    fn disabled_restricted_network() {
+        assert_eq!(disabled("", "netfilter"), None);
+        assert_eq!(
+            disabled("restricted-network yes\n", "netfilter"),
+            Some(("restricted-network", true))
+        );
+        assert_eq!(disabled("restricted-network yes\n", "net none"), None);
+        assert_eq!(
+            disabled("network no\nrestricted-network yes\n", "netfilter"),
+            Some(("network", false))
+        );
+    }
+}
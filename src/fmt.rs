@@ -0,0 +1,395 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for formatting profiles in the canonical order of firejail's profile.template
+
+use crate::cli::CliFormat;
+use crate::profile_stream::{Command, Content, Line, ProfileStream};
+use crate::utils::print_json;
+use log::{debug, error, info};
+use serde_json::json;
+use std::fs::{read_to_string, write};
+use std::process::exit;
+use std::sync::Arc;
+
+/// The sections of profile.template, in order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Section {
+    /// `include PROFILE.local`, `include globals.local` and `quiet`
+    Locals,
+    Ignore,
+    Noblacklist,
+    /// `include allow-*.inc`
+    Allow,
+    /// `blacklist` and `include disable-*.inc`
+    Blacklist,
+    Mkdir,
+    /// `whitelist` and `include whitelist-*.inc`
+    Whitelist,
+    /// `apparmor`, `caps.drop all`, `net none`, `no*`, `seccomp`, ...
+    Options,
+    /// `disable-mnt` and `private-*`
+    Private,
+    DBus,
+    /// `memory-deny-write-execute`, `read-only` and `read-write`
+    Misc,
+    /// `include OTHER.profile`
+    Redirect,
+}
+
+/// A command with the comments directly above it
+struct Block<'a> {
+    command: &'a Command,
+    /// `None` for commands which are kept in place, see [`format_profile`]
+    section: Option<Section>,
+    /// The position of the command inside its section
+    rank: u8,
+    lines: &'a [Line],
+}
+
+pub fn start(cli: &crate::cli::CliFmt, format: CliFormat) {
+    debug!("subcommand: fmt");
+
+    let mut unformatted = Vec::new();
+    let mut failed = false;
+    for file in &cli.files {
+        let data = match read_to_string(file) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Failed to read '{}': {}", file, err);
+                exit(1);
+            }
+        };
+        let profile_stream = match data.parse::<ProfileStream>() {
+            Ok(profile_stream) => profile_stream,
+            Err(_) => {
+                error!("Skipping '{}', it contains invalid lines.", file);
+                failed = true;
+                continue;
+            }
+        };
+
+        let formatted = format_profile(&profile_stream).to_string();
+        if formatted == data {
+            continue;
+        }
+        unformatted.push(file.as_str());
+        if !cli.check {
+            if let Err(err) = write(file, &formatted) {
+                error!("Failed to write '{}': {}", file, err);
+                failed = true;
+            }
+        }
+    }
+
+    if format == CliFormat::Json {
+        print_json(&json!({ "unformatted": unformatted }));
+    } else if cli.check {
+        for file in &unformatted {
+            println!("Would reformat: {}", file);
+        }
+    } else {
+        for file in &unformatted {
+            info!("Reformatted {}", file);
+        }
+    }
+
+    exit(if failed || (cli.check && !unformatted.is_empty()) {
+        1
+    } else {
+        0
+    });
+}
+
+/// Reorder `profile_stream` into the canonical order of profile.template
+///
+/// Comments are kept attached to the command they precede, the order of commands
+/// within a section is kept (except for the options). A leading comment block
+/// separated by a blank line stays at the top, trailing comments stay at the end.
+/// The sections are separated by exactly one blank line.
+///
+/// Commands which can not be classified (e.g. `include foo-common.inc` or `env`) are
+/// barriers: they keep their position and no command is moved across them, because
+/// the lines around them may depend on the order. `ignore`, `noblacklist` and
+/// `nowhitelist` become barriers too if sorting would move them across a line they
+/// affect (see [`affects`]).
+pub fn format_profile(profile_stream: &ProfileStream) -> ProfileStream {
+    let lines = profile_stream.as_slice();
+
+    let header_len = lines
+        .iter()
+        .position(|line| !line.is_comment())
+        .filter(|&end| end > 0 && *lines[end].content == Content::Blank)
+        .unwrap_or(0);
+    let (header, lines) = lines.split_at(header_len);
+
+    let mut blocks = Vec::new();
+    let mut start = None;
+    for (i, line) in lines.iter().enumerate() {
+        let command = match &*line.content {
+            // Blank lines are recreated between the sections, comments followed by
+            // a blank line are attached to the next command too.
+            Content::Blank => continue,
+            Content::Comment(_) => {
+                start.get_or_insert(i);
+                continue;
+            }
+            Content::Command(command) => command,
+            Content::Conditional(conditional) => conditional.command(),
+            Content::Invalid(..) => unreachable!("format_profile on a profile with errors"),
+        };
+        let (section, rank) = section(command).map_or((None, 0), |(s, r)| (Some(s), r));
+        let start = start.take().unwrap_or(i);
+        blocks.push(Block {
+            command,
+            section,
+            rank,
+            lines: &lines[start..=i],
+        });
+    }
+    let trailer = start.map_or(&[][..], |start| &lines[start..]);
+    let trailer = trailer.iter().filter(|line| line.is_comment());
+
+    let key = |block: &Block<'_>| (block.section, block.rank);
+    for i in 0..blocks.len() {
+        if blocks[i].section.is_none() {
+            continue;
+        }
+        // sort_by_key is stable, so only pairs with a decreasing key swap places.
+        let swaps = blocks.iter().enumerate().any(|(j, other)| {
+            other.section.is_some()
+                && affects(blocks[i].command, other.command)
+                && if j < i {
+                    key(&blocks[i]) < key(other)
+                } else {
+                    key(other) < key(&blocks[i])
+                }
+        });
+        if swaps {
+            blocks[i].section = None;
+        }
+    }
+
+    // sort_by_key is stable, so the order within a section is kept.
+    for chunk in blocks.split_mut(|block| block.section.is_none()) {
+        chunk.sort_by_key(|block| (block.section, block.rank));
+    }
+
+    let blank = || Line {
+        lineno: None,
+        content: Arc::new(Content::Blank),
+    };
+    let mut formatted = header.to_vec();
+    let mut previous = None;
+    for block in &blocks {
+        if previous.map_or(!formatted.is_empty(), |section| section != block.section) {
+            formatted.push(blank());
+        }
+        formatted.extend_from_slice(block.lines);
+        previous = Some(block.section);
+    }
+    let mut trailer = trailer.cloned().peekable();
+    if trailer.peek().is_some() && !formatted.is_empty() {
+        formatted.push(blank());
+    }
+    formatted.extend(trailer);
+
+    let mut profile_stream = formatted.into_iter().collect::<ProfileStream>();
+    profile_stream.rewrite_lineno();
+    profile_stream
+}
+
+/// Whether the order of `command` and `other` changes what the profile does
+///
+/// `ignore` ignores only the following lines which match it, `noblacklist` and
+/// `nowhitelist` only apply to the following `blacklist` and `whitelist` with the same
+/// path. Included files may contain anything.
+fn affects(command: &Command, other: &Command) -> bool {
+    use Command::*;
+
+    match (command, other) {
+        (Ignore(_) | Noblacklist(_) | Nowhitelist(_), Include(_)) => true,
+        (Ignore(pattern), other) => pattern.matches(other),
+        (Noblacklist(path), Blacklist(other) | BlacklistNolog(other)) => path == other,
+        (Nowhitelist(path), Whitelist(other)) => path == other,
+        _ => false,
+    }
+}
+
+/// The section of `command` and its rank inside that section, `None` if it can not be
+/// classified
+fn section(command: &Command) -> Option<(Section, u8)> {
+    use Command::*;
+
+    match command {
+        Include(include) if include.ends_with(".local") => Some((Section::Locals, 0)),
+        Include(include) if include.starts_with("allow-") => Some((Section::Allow, 0)),
+        Include(include) if include.starts_with("disable-") => Some((Section::Blacklist, 0)),
+        Include(include) if include.starts_with("whitelist-") => Some((Section::Whitelist, 0)),
+        Include(include) if include.ends_with(".profile") => Some((Section::Redirect, 0)),
+        Quiet => Some((Section::Locals, 0)),
+        Ignore(_) => Some((Section::Ignore, 0)),
+        Noblacklist(_) | Nowhitelist(_) => Some((Section::Noblacklist, 0)),
+        Blacklist(_) | BlacklistNolog(_) => Some((Section::Blacklist, 0)),
+        Mkdir(_) | Mkfile(_) => Some((Section::Mkdir, 0)),
+        Whitelist(_) => Some((Section::Whitelist, 0)),
+        AllowDebuggers => Some((Section::Options, 0)),
+        Allusers => Some((Section::Options, 1)),
+        Apparmor => Some((Section::Options, 2)),
+        Caps | CapsDropAll | CapsDrop(_) | CapsKeep(_) => Some((Section::Options, 3)),
+        IpcNamespace => Some((Section::Options, 4)),
+        MachineId => Some((Section::Options, 5)),
+        NetNone => Some((Section::Options, 6)),
+        Netfilter => Some((Section::Options, 7)),
+        No3d => Some((Section::Options, 8)),
+        Nodvd => Some((Section::Options, 9)),
+        Nogroups => Some((Section::Options, 10)),
+        Noinput => Some((Section::Options, 11)),
+        Nonewprivs => Some((Section::Options, 12)),
+        Noroot => Some((Section::Options, 13)),
+        Nosound => Some((Section::Options, 14)),
+        Notv => Some((Section::Options, 15)),
+        Nou2f => Some((Section::Options, 16)),
+        Novideo => Some((Section::Options, 17)),
+        Protocol(_) => Some((Section::Options, 18)),
+        Seccomp(_) | SeccompDrop(_) => Some((Section::Options, 19)),
+        SeccompBlockSecondary => Some((Section::Options, 20)),
+        SeccompErrorAction(_) => Some((Section::Options, 21)),
+        ShellNone => Some((Section::Options, 22)),
+        Tracelog => Some((Section::Options, 23)),
+        X11(_) | X11None => Some((Section::Options, 24)),
+        DisableMnt => Some((Section::Private, 0)),
+        Private(_) | PrivateHome(_) => Some((Section::Private, 1)),
+        PrivateBin(_) => Some((Section::Private, 2)),
+        PrivateCache => Some((Section::Private, 3)),
+        PrivateCwd(_) => Some((Section::Private, 4)),
+        PrivateDev => Some((Section::Private, 5)),
+        PrivateEtc(_) => Some((Section::Private, 6)),
+        PrivateLib(_) => Some((Section::Private, 7)),
+        PrivateOpt(_) => Some((Section::Private, 8)),
+        PrivateSrv(_) => Some((Section::Private, 9)),
+        PrivateTmp => Some((Section::Private, 10)),
+        DBusUser(_) | DBusUserOwn(_) | DBusUserTalk(_) => Some((Section::DBus, 0)),
+        DBusSystem(_) | DBusSystemOwn(_) | DBusSystemTalk(_) => Some((Section::DBus, 1)),
+        MemoryDenyWriteExecute => Some((Section::Misc, 0)),
+        ReadOnly(_) | ReadWrite(_) => Some((Section::Misc, 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_canonical_order() {
+        let profile = "# header\n\nnoroot\n# keep foo\nwhitelist /foo\ninclude foo.local\n\nblacklist /bar\n# end\n";
+        let formatted = format_profile(&profile.parse().unwrap()).to_string();
+        assert_eq!(
+            formatted,
+            "# header\n\ninclude foo.local\n\nblacklist /bar\n\n# keep foo\nwhitelist /foo\n\nnoroot\n\n# end\n",
+        );
+        assert_eq!(
+            format_profile(&formatted.parse().unwrap()).to_string(),
+            formatted
+        );
+    }
+
+    fn format(profile: &str) -> String {
+        let formatted = format_profile(&profile.parse().unwrap()).to_string();
+        assert_eq!(
+            format_profile(&formatted.parse().unwrap()).to_string(),
+            formatted
+        );
+        formatted
+    }
+
+    #[test]
+    fn format_custom_include_is_barrier() {
+        assert_eq!(
+            format("include foo-common.inc\nnoroot\ninclude foo.local\nblacklist /bar\n"),
+            "include foo-common.inc\n\ninclude foo.local\n\nblacklist /bar\n\nnoroot\n",
+        );
+        assert_eq!(
+            format("noroot\n# electron\ninclude electron.inc\nblacklist /bar\n"),
+            "noroot\n\n# electron\ninclude electron.inc\n\nblacklist /bar\n",
+        );
+    }
+
+    #[test]
+    fn format_unclassified_command_is_barrier() {
+        assert_eq!(
+            format("nosound\nnoroot\nenv FOO=1\nenv BAR=2\nprivate-tmp\nnogroups\n"),
+            "noroot\nnosound\n\nenv FOO=1\nenv BAR=2\n\nnogroups\n\nprivate-tmp\n",
+        );
+    }
+
+    #[test]
+    fn format_conditionals() {
+        assert_eq!(
+            format("private-dev\n?HAS_X11: ignore nosound\nnoroot\n?BROWSER_DISABLE_U2F: nou2f\n"),
+            "?HAS_X11: ignore nosound\n\nnoroot\n?BROWSER_DISABLE_U2F: nou2f\n\nprivate-dev\n",
+        );
+    }
+
+    #[test]
+    fn format_keeps_ignores_after_matched_lines() {
+        assert_eq!(
+            format("noroot\nignore noroot\nnosound\n"),
+            "noroot\n\nignore noroot\n\nnosound\n",
+        );
+        assert_eq!(
+            format("private-dev\n?HAS_X11: ignore private-dev\nnoroot\n"),
+            "private-dev\n\n?HAS_X11: ignore private-dev\n\nnoroot\n",
+        );
+        assert_eq!(
+            format("include disable-common.inc\nignore noexec\n"),
+            "include disable-common.inc\n\nignore noexec\n",
+        );
+        // Ignores which already come first keep sorting the other lines
+        assert_eq!(
+            format("ignore noroot\nnoroot\nnosound\nblacklist /foo\n"),
+            "ignore noroot\n\nblacklist /foo\n\nnoroot\nnosound\n",
+        );
+    }
+
+    #[test]
+    fn format_keeps_noblacklist_after_blacklist() {
+        assert_eq!(
+            format("blacklist ${HOME}/.ssh\nnoblacklist ${HOME}/.ssh\nnoroot\n"),
+            "blacklist ${HOME}/.ssh\n\nnoblacklist ${HOME}/.ssh\n\nnoroot\n",
+        );
+        assert_eq!(
+            format("whitelist /foo\nnowhitelist /foo\nnowhitelist /bar\n"),
+            "whitelist /foo\n\nnowhitelist /foo\n\nnowhitelist /bar\n",
+        );
+        assert_eq!(
+            format("blacklist /foo\nnoblacklist /bar\n"),
+            "noblacklist /bar\n\nblacklist /foo\n",
+        );
+    }
+
+    #[test]
+    fn format_comments() {
+        assert_eq!(
+            format("# header\n\n# why\nnoroot\n# sound\n\nnosound\ninclude foo.local\n# trailer\n"),
+            "# header\n\ninclude foo.local\n\n# why\nnoroot\n# sound\n\nnosound\n\n# trailer\n",
+        );
+    }
+}
@@ -17,16 +17,29 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::cli::CliFormat;
+use crate::fatal;
 use crate::profile::{Profile, ProfileFlags};
-use crate::utils::ColoredText;
+use crate::utils::{print_json, ColoredText};
 use log::debug;
+use serde_json::json;
 use std::process::exit;
 use termcolor::Color;
 
-pub fn start(cli: &crate::cli::CliHas) {
+pub fn start(cli: &crate::cli::CliHas, format: CliFormat) {
     debug!("subcommand: has");
 
-    let profile = Profile::new(&cli.profile_name, ProfileFlags::default()).unwrap();
+    let profile = Profile::new(&cli.profile_name, ProfileFlags::default())
+        .unwrap_or_else(|err| fatal!("{}", err));
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "name": profile.raw_name(),
+            "full_name": profile.full_name(),
+            "path": profile.path(),
+        }));
+        exit(if profile.path().is_some() { 0 } else { 100 });
+    }
+
     if let Some(path) = profile.path() {
         println!(
             "Profile found for {} at {}",
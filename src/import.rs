@@ -0,0 +1,59 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::cli::CliExportFormat;
+use crate::export::deserialize;
+use crate::fatal;
+use log::debug;
+use std::fs::{read_to_string, File};
+use std::io::{stdout, Write};
+use std::path::Path;
+
+pub fn start(cli: &crate::cli::CliImport) {
+    debug!("subcommand: import");
+
+    let format = cli.from.unwrap_or_else(|| {
+        match Path::new(&cli.file)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("json") => CliExportFormat::Json,
+            Some("yaml") | Some("yml") => CliExportFormat::Yaml,
+            Some("toml") => CliExportFormat::Toml,
+            _ => fatal!(
+                "Could not guess the format of '{}', please specify it with --from.",
+                cli.file
+            ),
+        }
+    });
+
+    let data = read_to_string(&cli.file)
+        .unwrap_or_else(|err| fatal!("Failed to read '{}': {}", cli.file, err));
+    let profile_stream = deserialize(&data, format)
+        .unwrap_or_else(|err| fatal!("Failed to import '{}': {}", cli.file, err));
+
+    if let Some(output_file) = &cli.output_file {
+        File::create(output_file)
+            .and_then(|mut file| write!(file, "{}", profile_stream))
+            .unwrap_or_else(|err| fatal!("Failed to write '{}': {}", output_file, err));
+    } else {
+        print!("{}", profile_stream);
+        stdout().flush().unwrap();
+    }
+}
@@ -17,19 +17,192 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use crate::{fatal, USER_PROFILE_DIR};
+use crate::cli::CliFormat;
+use crate::disable::DISABLED_DIR;
+use crate::location::Location;
+use crate::profile::GLOBALS;
+use crate::profile_stream::{Command, Content, ProfileStream};
+use crate::utils::print_json;
+use crate::{fatal, SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
 use log::{debug, warn};
-use std::ffi::OsStr;
-use std::fs::read_dir;
-use std::io::{stdout, Write};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{read, read_dir, read_to_string};
+use std::io::{stdout, ErrorKind, Write};
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 
-pub fn start(cli: &crate::cli::CliList) {
+/// The status of a profile in all locations
+#[derive(Debug, Serialize)]
+struct Entry {
+    /// The file name converted lossily for JSON
+    name: String,
+    /// The file name as it is on disk
+    #[serde(skip)]
+    file_name: OsString,
+    system: bool,
+    user: bool,
+    /// Whether the user profile differs from the system profile,
+    /// `None` if it does not exist in both locations or they could not be read
+    modified: Option<bool>,
+    /// `None` if `name` is not a `.profile`
+    local: Option<bool>,
+    disabled: bool,
+    /// `None` if `name` is not a `.profile` or it could not be read
+    redirect: Option<bool>,
+    /// The included `.profile`s, `None` if `name` is not a `.profile` or it could not be read
+    redirects: Option<Vec<String>>,
+    /// `None` if the profile could not be read
+    errors: Option<usize>,
+}
+
+pub fn start(cli: &crate::cli::CliList, format: CliFormat) {
     debug!("subcommand: list");
 
-    let mut user_profiles = read_dir(&*USER_PROFILE_DIR)
-        .unwrap_or_else(|err| fatal!("Failed to open the user profile directory: {}", err))
+    let user_profiles = read_file_names(&USER_PROFILE_DIR)
+        .unwrap_or_else(|err| fatal!("Failed to open the user profile directory: {}", err));
+    let system_profiles = read_file_names(&SYSTEM_PROFILE_DIR).unwrap_or_else(|err| {
+        warn!("Failed to open the system profile directory: {}", err);
+        BTreeSet::new()
+    });
+    let disabled_profiles = read_file_names(&DISABLED_DIR).unwrap_or_else(|err| {
+        warn!("Failed to open the disabled profile directory: {}", err);
+        BTreeSet::new()
+    });
+
+    let names: BTreeSet<&OsString> = if cli.system {
+        system_profiles.iter().collect()
+    } else if cli.disabled {
+        disabled_profiles.iter().collect()
+    } else if cli.all {
+        user_profiles
+            .iter()
+            .chain(&system_profiles)
+            .chain(&disabled_profiles)
+            .collect()
+    } else {
+        user_profiles.iter().collect()
+    };
+
+    let has = |name: &OsStr| user_profiles.contains(name) || system_profiles.contains(name);
+
+    let entries = names
+        .into_iter()
+        .filter(|name| !cli.incs || has_extension(name, "inc"))
+        .filter(|name| !cli.locals || has_extension(name, "local"))
+        .filter(|name| !cli.profiles || has_extension(name, "profile"))
+        .filter(|name| {
+            !cli.overridden || (user_profiles.contains(*name) && system_profiles.contains(*name))
+        })
+        .filter(|name| {
+            !cli.orphaned_locals
+                || (has_extension(name, "local")
+                    && !name.to_str().map_or(false, |name| GLOBALS.contains(&name))
+                    && !has(&with_extension(name, "profile"))
+                    && !has(&with_extension(name, "inc")))
+        })
+        .map(|name| {
+            let system = system_profiles.contains(name);
+            let user = user_profiles.contains(name);
+            let disabled = disabled_profiles.contains(name);
+            let local = has_extension(name, "profile").then(|| has(&with_extension(name, "local")));
+            let (redirects, errors) = if cli.long || cli.redirects {
+                let location: &Location = if user {
+                    &USER_PROFILE_DIR
+                } else if system {
+                    &SYSTEM_PROFILE_DIR
+                } else {
+                    &DISABLED_DIR
+                };
+                inspect(&location.get_ref().join(name))
+            } else {
+                (None, None)
+            };
+            let redirect = redirects.as_ref().map(|redirects| !redirects.is_empty());
+            let modified = if cli.long && user && system {
+                is_modified(
+                    &USER_PROFILE_DIR.get_ref().join(name),
+                    &SYSTEM_PROFILE_DIR.get_ref().join(name),
+                )
+            } else {
+                None
+            };
+
+            Entry {
+                name: name.to_string_lossy().into_owned(),
+                file_name: name.clone(),
+                system,
+                user,
+                modified,
+                local,
+                disabled,
+                redirect,
+                redirects,
+                errors,
+            }
+        })
+        .filter(|entry| !cli.redirects || entry.redirect == Some(true))
+        .collect::<Vec<_>>();
+
+    if format == CliFormat::Json {
+        print_json(&json!({ "profiles": entries }));
+    } else if cli.long {
+        print_table(&entries);
+    } else if cli.redirects {
+        let stdout = stdout();
+        let mut stdout = stdout.lock();
+        for entry in entries {
+            stdout.write_all(entry.file_name.as_bytes()).unwrap();
+            writeln!(
+                stdout,
+                " -> {}",
+                entry.redirects.unwrap_or_default().join(", ")
+            )
+            .unwrap();
+        }
+    } else {
+        let stdout = stdout();
+        let mut stdout = stdout.lock();
+        for entry in entries {
+            stdout.write_all(entry.file_name.as_bytes()).unwrap();
+            stdout.write_all(b"\n").unwrap();
+        }
+    }
+}
+
+/// Read the names of all files in `location` which are valid UTF-8
+///
+/// Other names can not be profiles, they are skipped with a warning.
+pub fn read_names(location: &Location) -> std::io::Result<BTreeSet<String>> {
+    Ok(read_file_names(location)?
+        .into_iter()
+        .filter_map(|name| {
+            name.into_string()
+                .map_err(|name| {
+                    warn!(
+                        "Skipping '{}', it is not valid UTF-8.",
+                        Path::new(&name).display()
+                    )
+                })
+                .ok()
+        })
+        .collect())
+}
+
+/// Read the names of all files in `location`
+///
+/// Directories and hidden files are skipped, a `location` which does not exist is
+/// treated as empty.
+fn read_file_names(location: &Location) -> std::io::Result<BTreeSet<OsString>> {
+    let dir = match read_dir(location) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(err) => return Err(err),
+    };
+
+    Ok(dir
         .filter_map(|readdir_result| match readdir_result {
             Ok(direntry) => Some(direntry),
             Err(e) => {
@@ -37,17 +210,175 @@ pub fn start(cli: &crate::cli::CliList) {
                 None
             }
         })
-        .filter(|direntry| direntry.file_type().unwrap().is_file())
-        .map(|file| file.file_name())
-        .filter(|file| !cli.incs || Path::new(file).extension() == Some(OsStr::new("inc")))
-        .filter(|file| !cli.locals || Path::new(file).extension() == Some(OsStr::new("local")))
-        .filter(|file| !cli.profiles || Path::new(file).extension() == Some(OsStr::new("profile")))
-        .collect::<Vec<_>>();
-    user_profiles.sort_unstable();
+        .filter(|direntry| !direntry.file_type().map_or(true, |ft| ft.is_dir()))
+        .map(|direntry| direntry.file_name())
+        .filter(|name| !name.as_bytes().starts_with(b"."))
+        .collect())
+}
+
+fn has_extension(name: &OsStr, extension: &str) -> bool {
+    Path::new(name).extension() == Some(OsStr::new(extension))
+}
+
+/// `name` with its extension replaced by `extension`
+fn with_extension(name: &OsStr, extension: &str) -> OsString {
+    Path::new(name).with_extension(extension).into_os_string()
+}
+
+/// Parse the profile at `path` and return the `.profile`s it includes
+/// and the number of invalid lines
+fn inspect(path: &Path) -> (Option<Vec<String>>, Option<usize>) {
+    let content = match read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            warn!("Failed to read '{}': {}", path.display(), err);
+            return (None, None);
+        }
+    };
+    let profile_stream = content
+        .parse::<ProfileStream>()
+        .unwrap_or_else(|profile_stream| profile_stream);
+
+    let redirects = path
+        .extension()
+        .map_or(false, |ext| ext == "profile")
+        .then(|| {
+            profile_stream
+                .iter()
+                .filter_map(|line| match &*line.content {
+                    Content::Command(Command::Include(other_profile))
+                        if other_profile.ends_with(".profile") =>
+                    {
+                        Some(other_profile.clone())
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+    let errors = profile_stream
+        .iter()
+        .filter(|line| !line.is_valid())
+        .count();
+
+    (redirects, Some(errors))
+}
+
+/// Compare the user profile at `user` with the system profile at `system`
+fn is_modified(user: &Path, system: &Path) -> Option<bool> {
+    match (read(user), read(system)) {
+        (Ok(user), Ok(system)) => Some(user != system),
+        (Err(err), _) | (_, Err(err)) => {
+            warn!("Failed to compare '{}': {}", user.display(), err);
+            None
+        }
+    }
+}
+
+fn print_table(entries: &[Entry]) {
+    fn yes_no(value: Option<bool>) -> &'static str {
+        match value {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "-",
+        }
+    }
+
+    let width = entries
+        .iter()
+        .map(|entry| entry.file_name.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
     let stdout = stdout();
     let mut stdout = stdout.lock();
-    for user_profile in user_profiles {
-        stdout.write_all(user_profile.as_bytes()).unwrap();
-        stdout.write_all(b"\n").unwrap();
+    writeln!(
+        stdout,
+        "{:width$}  SYSTEM  USER  MODIFIED  LOCAL  DISABLED  REDIRECT  ERRORS",
+        "NAME",
+        width = width,
+    )
+    .unwrap();
+    for entry in entries {
+        stdout.write_all(entry.file_name.as_bytes()).unwrap();
+        writeln!(
+            stdout,
+            "{:padding$}  {:6}  {:4}  {:8}  {:5}  {:8}  {:8}  {}",
+            "",
+            yes_no(Some(entry.system)),
+            yes_no(Some(entry.user)),
+            yes_no(entry.modified),
+            yes_no(entry.local),
+            yes_no(Some(entry.disabled)),
+            yes_no(entry.redirect),
+            entry
+                .errors
+                .map_or_else(|| "-".to_string(), |errors| errors.to_string()),
+            padding = width - entry.file_name.len(),
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+    use std::fs::{create_dir, File};
+
+    #[test]
+    fn read_file_names_keeps_non_utf8_names() {
+        let dir = TempDir::new();
+        dir.write("foo.profile", "");
+        dir.write(".hidden.profile", "");
+        create_dir(dir.path().join("disabled")).unwrap();
+        File::create(dir.path().join(OsStr::from_bytes(b"b\xffr.inc"))).unwrap();
+
+        let names = read_file_names(&Location::from(dir.path())).unwrap();
+        assert_eq!(
+            names.into_iter().collect::<Vec<_>>(),
+            [OsStr::from_bytes(b"b\xffr.inc"), OsStr::new("foo.profile")]
+        );
+        let names = read_names(&Location::from(dir.path())).unwrap();
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), ["foo.profile"]);
+        assert!(read_file_names(&Location::from(dir.path().join("missing")))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn is_modified_compares_the_content() {
+        let dir = TempDir::new();
+        let system = dir.write("system/foo.profile", "noroot\n");
+        let copy = dir.write("user/foo.profile", "noroot\n");
+        assert_eq!(is_modified(&copy, &system), Some(false));
+        let changed = dir.write("user/foo.profile", "noroot\nquiet\n");
+        assert_eq!(is_modified(&changed, &system), Some(true));
+        assert_eq!(is_modified(&dir.path().join("missing"), &system), None);
+    }
+
+    #[test]
+    fn inspect_lists_included_profiles() {
+        let dir = TempDir::new();
+        let redirect = dir.write(
+            "foo.profile",
+            "include foo.local\ninclude foo-common.profile\ninclude disable-common.inc\n\
+             include bar.profile\nfoo bar\n",
+        );
+        assert_eq!(
+            inspect(&redirect),
+            (
+                Some(vec![
+                    "foo-common.profile".to_string(),
+                    "bar.profile".to_string()
+                ]),
+                Some(1)
+            )
+        );
+        let plain = dir.write("bar.profile", "include disable-common.inc\nnoroot\n");
+        assert_eq!(inspect(&plain), (Some(Vec::new()), Some(0)));
+        let inc = dir.write("foo.inc", "include bar.profile\n");
+        assert_eq!(inspect(&inc), (None, Some(0)));
+        assert_eq!(inspect(&dir.path().join("missing.profile")), (None, None));
     }
 }
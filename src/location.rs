@@ -22,6 +22,8 @@
 #![allow(dead_code)]
 
 use std::convert::From;
+use std::env::split_paths;
+use std::ffi::OsStr;
 use std::fmt;
 use std::io::Result as IoResult;
 use std::path::{Path, PathBuf};
@@ -75,6 +77,40 @@ impl Location {
         &self.inner
     }
 
+    /// Split a colon separated list of directories (like `PATH`) into `Location`s
+    ///
+    /// Empty entries are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert_eq!(
+    ///     Location::from_path_list("/srv/firejail::/opt/firejail"),
+    ///     [Location::from("/srv/firejail"), Location::from("/opt/firejail")],
+    /// );
+    /// ```
+    pub fn from_path_list<T: AsRef<OsStr> + ?Sized>(list: &T) -> Vec<Self> {
+        split_paths(list)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(Self::from)
+            .collect()
+    }
+
+    /// The additional profile dirs: `cli_dirs` (`--profile-dir`), the dirs from `profile_path`
+    /// (`$FJP_PROFILE_PATH`) and `config_dirs` (`profile-dirs.extra`), in this order
+    pub fn extra_dirs(
+        cli_dirs: &[PathBuf],
+        profile_path: Option<&OsStr>,
+        config_dirs: Vec<PathBuf>,
+    ) -> Vec<Self> {
+        cli_dirs
+            .iter()
+            .map(Self::from)
+            .chain(profile_path.map(Self::from_path_list).unwrap_or_default())
+            .chain(config_dirs.into_iter().map(Self::from))
+            .collect()
+    }
+
     /// Clone the inner PathBuf and return it
     pub fn to_owned_inner(&self) -> PathBuf {
         self.inner.to_path_buf()
@@ -116,6 +152,36 @@ mod tests {
         assert_eq!(Location::from("/").get_ref(), Path::new("/"));
     }
 
+    #[test]
+    fn test_from_path_list() {
+        assert_eq!(
+            Location::from_path_list("/srv/firejail::/opt/firejail:"),
+            [
+                Location::from("/srv/firejail"),
+                Location::from("/opt/firejail")
+            ],
+        );
+        assert_eq!(Location::from_path_list(""), []);
+    }
+
+    #[test]
+    fn test_extra_dirs() {
+        assert_eq!(
+            Location::extra_dirs(
+                &[PathBuf::from("/cli")],
+                Some(OsStr::new("/env1:/env2")),
+                vec![PathBuf::from("/config")],
+            ),
+            [
+                Location::from("/cli"),
+                Location::from("/env1"),
+                Location::from("/env2"),
+                Location::from("/config"),
+            ],
+        );
+        assert_eq!(Location::extra_dirs(&[], None, Vec::new()), []);
+    }
+
     #[test]
     fn test_to_owned_inner() {
         assert_eq!(Location::from("/").to_owned_inner(), PathBuf::from("/"));
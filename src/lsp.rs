@@ -0,0 +1,551 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for the language server (LSP over stdio)
+
+use crate::effective::LOOKUP_FLAGS;
+use crate::fatal;
+use crate::fmt::format_profile;
+use crate::list::read_names;
+use crate::location::Location as ProfileLocation;
+use crate::profile::{search_path, Profile, ProfileFlags};
+use crate::profile_stream::{
+    Capabilities, Command, Content, DBusPolicy, ProfileStream, Protocol, SeccompErrorAction,
+    X11Server, COMMANDS,
+};
+use crate::validate::include_exists;
+use log::{debug, info};
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, Formatting, GotoDefinition, HoverRequest};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    CompletionTextEdit, Diagnostic, DiagnosticSeverity, DocumentFormattingParams,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, NumberOrString,
+    OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// All conditions with a short description
+const CONDITIONS: &[(&str, &str)] = &[
+    ("?ALLOW_TRAY:", "`allow-tray yes` in firejail.config"),
+    (
+        "?BROWSER_ALLOW_DRM:",
+        "`browser-allow-drm yes` in firejail.config",
+    ),
+    (
+        "?BROWSER_DISABLE_U2F:",
+        "`browser-disable-u2f yes` in firejail.config",
+    ),
+    ("?HAS_APPIMAGE:", "The sandbox runs an AppImage"),
+    ("?HAS_NET:", "The sandbox has a network namespace (`--net`)"),
+    ("?HAS_NODBUS:", "D-Bus is disabled (`--nodbus`)"),
+    ("?HAS_NOSOUND:", "Sound is disabled (`--nosound`)"),
+    (
+        "?HAS_PRIVATE:",
+        "The home directory is private (`--private`)",
+    ),
+    ("?HAS_X11:", "The sandbox has its own X11 server (`--x11`)"),
+];
+
+pub fn start(_cli: &crate::cli::CliLsp) {
+    debug!("subcommand: lsp");
+
+    run().unwrap_or_else(|err| fatal!("Language server failed: {}", err));
+}
+
+fn run() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![" ".to_string(), ",".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let _params: InitializeParams = serde_json::from_value(params)?;
+    info!("Language server initialized.");
+
+    let mut documents: HashMap<Url, String> = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = handle_request(request, &documents);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(notification, &mut documents)? {
+                    let diagnostics = documents
+                        .get(&uri)
+                        .map_or_else(Vec::new, |text| diagnostics(&uri, text));
+                    let params = PublishDiagnosticsParams {
+                        uri,
+                        diagnostics,
+                        version: None,
+                    };
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_string(),
+                            params,
+                        )))?;
+                }
+            }
+            Message::Response(_) => (),
+        }
+    }
+
+    // The writer thread only stops once all senders are gone.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Update `documents` and return the uri of the changed document
+fn handle_notification(
+    notification: Notification,
+    documents: &mut HashMap<Url, String>,
+) -> anyhow::Result<Option<Url>> {
+    Ok(match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: <DidOpenTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), params.text_document.text);
+            Some(uri)
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: <DidChangeTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            if let Some(change) = params.content_changes.into_iter().last() {
+                documents.insert(uri.clone(), change.text);
+            }
+            Some(uri)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: <DidCloseTextDocument as NotificationTrait>::Params =
+                serde_json::from_value(notification.params)?;
+            documents.remove(&params.text_document.uri);
+            // Clear the diagnostics of the closed document.
+            Some(params.text_document.uri)
+        }
+        _ => None,
+    })
+}
+
+fn handle_request(request: Request, documents: &HashMap<Url, String>) -> Response {
+    fn respond<R: serde::Serialize>(id: RequestId, result: anyhow::Result<R>) -> Response {
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(err) => Response::new_err(
+                id,
+                lsp_server::ErrorCode::InvalidParams as i32,
+                err.to_string(),
+            ),
+        }
+    }
+
+    let id = request.id.clone();
+    match request.method.as_str() {
+        <Completion as lsp_types::request::Request>::METHOD => respond(
+            id,
+            serde_json::from_value::<CompletionParams>(request.params)
+                .map_err(Into::into)
+                .map(|params| completion(&params, documents)),
+        ),
+        <HoverRequest as lsp_types::request::Request>::METHOD => respond(
+            id,
+            serde_json::from_value::<HoverParams>(request.params)
+                .map_err(Into::into)
+                .map(|params| hover(&params, documents)),
+        ),
+        <GotoDefinition as lsp_types::request::Request>::METHOD => respond(
+            id,
+            serde_json::from_value::<GotoDefinitionParams>(request.params)
+                .map_err(Into::into)
+                .map(|params| definition(&params, documents)),
+        ),
+        <Formatting as lsp_types::request::Request>::METHOD => respond(
+            id,
+            serde_json::from_value::<DocumentFormattingParams>(request.params)
+                .map_err(Into::into)
+                .map(|params| formatting(&params, documents)),
+        ),
+        method => Response::new_err(
+            id,
+            lsp_server::ErrorCode::MethodNotFound as i32,
+            format!("Unsupported method: {}", method),
+        ),
+    }
+}
+
+/// The length of `s` in UTF-16 code units, as used by LSP positions
+fn utf16_len(s: &str) -> u32 {
+    s.encode_utf16().count() as u32
+}
+
+/// The byte offset in `line` of the UTF-16 based `character`
+fn byte_offset(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character {
+            return offset;
+        }
+        units += c.len_utf16() as u32;
+    }
+    line.len()
+}
+
+/// The line `position` points to
+fn line_at<'a>(text: &'a str, position: &Position) -> Option<&'a str> {
+    text.lines().nth(position.line as usize)
+}
+
+/// Strip a leading condition (`?HAS_X11: `) and `ignore ` from `line`
+fn strip_prefixes(line: &str) -> &str {
+    let mut line = line;
+    if line.starts_with('?') {
+        if let Some((_, rest)) = line.split_once(": ") {
+            line = rest;
+        }
+    }
+    line.strip_prefix("ignore ").unwrap_or(line)
+}
+
+fn diagnostics(uri: &Url, text: &str) -> Vec<Diagnostic> {
+    let file = uri
+        .to_file_path()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let profile_stream = text.parse::<ProfileStream>().unwrap_or_else(|ps| ps);
+
+    profile_stream
+        .iter()
+        .filter_map(|line| {
+            let lineno = line.lineno? as u32;
+            let (severity, code, message) = match &*line.content {
+                Content::Invalid(invalid, err) => (
+                    DiagnosticSeverity::ERROR,
+                    serde_json::to_value(err).ok()?.as_str()?.to_string(),
+                    format!("{}: {}", err, invalid),
+                ),
+                Content::Command(Command::Include(include))
+                    if !file.is_empty() && !include_exists(&file, include) =>
+                {
+                    (
+                        DiagnosticSeverity::WARNING,
+                        "missing-include".to_string(),
+                        format!("Included file not found: {}", include),
+                    )
+                }
+                _ => return None,
+            };
+            let len = utf16_len(text.lines().nth(lineno as usize).unwrap_or_default());
+            Some(Diagnostic {
+                range: Range::new(Position::new(lineno, 0), Position::new(lineno, len)),
+                severity: Some(severity),
+                code: Some(NumberOrString::String(code)),
+                source: Some("fjp".to_string()),
+                message,
+                ..Diagnostic::default()
+            })
+        })
+        .collect()
+}
+
+fn completion(
+    params: &CompletionParams,
+    documents: &HashMap<Url, String>,
+) -> Option<CompletionResponse> {
+    let position = &params.text_document_position.position;
+    let uri = &params.text_document_position.text_document.uri;
+    let line = documents
+        .get(uri)
+        .and_then(|text| line_at(text, position))
+        .unwrap_or_default();
+    let prefix = &line[..byte_offset(line, position.character)];
+
+    // The word which is completed, it starts after the last ' ' or ','.
+    let word_start = prefix.rfind(|c| c == ' ' || c == ',').map_or(0, |i| i + 1);
+    let word = &prefix[word_start..];
+    let range = Range::new(
+        Position::new(position.line, utf16_len(&prefix[..word_start])),
+        *position,
+    );
+    let item = |label: &str, kind: CompletionItemKind, detail: Option<&str>| CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: detail.map(String::from),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            range,
+            label.to_string(),
+        ))),
+        ..CompletionItem::default()
+    };
+
+    let command = strip_prefixes(prefix);
+    let items: Vec<CompletionItem> = if prefix.starts_with('?') && !prefix.contains(' ') {
+        CONDITIONS
+            .iter()
+            .map(|(condition, doc)| item(condition, CompletionItemKind::KEYWORD, Some(doc)))
+            .collect()
+    } else if !command.contains(' ') {
+        // Also complete `net none` and friends, so the range starts at the command.
+        let range = Range::new(
+            Position::new(
+                position.line,
+                utf16_len(&prefix[..prefix.len() - command.len()]),
+            ),
+            *position,
+        );
+        COMMANDS
+            .iter()
+            .filter(|(name, _)| name.starts_with(command))
+            .map(|(name, doc)| CompletionItem {
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                    range,
+                    name.to_string(),
+                ))),
+                ..item(name, CompletionItemKind::KEYWORD, Some(doc))
+            })
+            .collect()
+    } else {
+        let values: Vec<String> = match command.split_once(' ').map(|(name, _)| name) {
+            Some("caps.drop") | Some("caps.keep") => {
+                Capabilities::VALUES.iter().map(|v| v.to_string()).collect()
+            }
+            Some("protocol") => Protocol::VALUES.iter().map(|v| v.to_string()).collect(),
+            Some("seccomp-error-action") => SeccompErrorAction::VALUES
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            Some("dbus-user") | Some("dbus-system") => {
+                DBusPolicy::VALUES.iter().map(|v| v.to_string()).collect()
+            }
+            Some("include") => known_includes(uri).into_iter().collect(),
+            Some("x11") => X11Server::VALUES.iter().map(|v| v.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        values
+            .iter()
+            .filter(|value| value.starts_with(word))
+            .map(|value| item(value, CompletionItemKind::VALUE, None))
+            .collect()
+    };
+
+    Some(CompletionResponse::Array(items))
+}
+
+/// All files which can be included from `uri`
+fn known_includes(uri: &Url) -> BTreeSet<String> {
+    let mut locations = search_path(LOOKUP_FLAGS)
+        .cloned()
+        .collect::<Vec<ProfileLocation>>();
+    if let Some(dir) = uri.to_file_path().ok().as_deref().and_then(Path::parent) {
+        locations.push(ProfileLocation::from(dir));
+    }
+    locations
+        .iter()
+        .filter_map(|location| read_names(location).ok())
+        .flatten()
+        .filter(|name| {
+            name.ends_with(".inc") || name.ends_with(".local") || name.ends_with(".profile")
+        })
+        .collect()
+}
+
+fn hover(params: &HoverParams, documents: &HashMap<Url, String>) -> Option<Hover> {
+    let position = &params.text_document_position_params.position;
+    let line = documents
+        .get(&params.text_document_position_params.text_document.uri)
+        .and_then(|text| line_at(text, position))?;
+
+    let (name, doc) = if line.starts_with('?')
+        && byte_offset(line, position.character) < line.find(' ').unwrap_or(line.len())
+    {
+        CONDITIONS
+            .iter()
+            .find(|(condition, _)| line.starts_with(condition))?
+    } else {
+        let command = strip_prefixes(line);
+        COMMANDS
+            .iter()
+            .filter(|(name, _)| {
+                command == *name
+                    || command
+                        .strip_prefix(name)
+                        .map_or(false, |rest| rest.starts_with(' '))
+            })
+            .max_by_key(|(name, _)| name.len())?
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("**{}**\n\n{}", name, doc),
+        }),
+        range: None,
+    })
+}
+
+fn definition(
+    params: &GotoDefinitionParams,
+    documents: &HashMap<Url, String>,
+) -> Option<GotoDefinitionResponse> {
+    let position = &params.text_document_position_params.position;
+    let uri = &params.text_document_position_params.text_document.uri;
+    let line = documents
+        .get(uri)
+        .and_then(|text| line_at(text, position))?;
+    let include = strip_prefixes(line).strip_prefix("include ")?.trim();
+
+    let sibling = uri
+        .to_file_path()
+        .ok()
+        .as_deref()
+        .and_then(Path::parent)
+        .map(|dir| dir.join(include))
+        .filter(|path| path.exists());
+    let path: PathBuf = match sibling {
+        Some(path) => path,
+        None if include.contains('/') => PathBuf::from(include),
+        None => Profile::new(include, LOOKUP_FLAGS.without(ProfileFlags::READ))
+            .ok()?
+            .try_into_pathbuf()?,
+    };
+
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        Url::from_file_path(path).ok()?,
+        Range::default(),
+    )))
+}
+
+fn formatting(
+    params: &DocumentFormattingParams,
+    documents: &HashMap<Url, String>,
+) -> Option<Vec<TextEdit>> {
+    let text = documents.get(&params.text_document.uri)?;
+    // Do not touch profiles with invalid lines, formatting would not be lossless.
+    let formatted = format_profile(&text.parse::<ProfileStream>().ok()?).to_string();
+    if formatted == *text {
+        return Some(Vec::new());
+    }
+
+    let end = Position::new(text.lines().count() as u32 + 1, 0);
+    Some(vec![TextEdit::new(
+        Range::new(Position::new(0, 0), end),
+        formatted,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_utf16_len() {
+        assert_eq!(utf16_len("noroot"), 6);
+        assert_eq!(utf16_len("ä"), 1);
+        assert_eq!(utf16_len("ä😀"), 3);
+    }
+
+    #[test]
+    fn test_byte_offset() {
+        let line = "ä😀x";
+        assert_eq!(byte_offset(line, 0), 0);
+        assert_eq!(byte_offset(line, 1), 2);
+        assert_eq!(byte_offset(line, 3), 6);
+        assert_eq!(byte_offset(line, 4), 7);
+        assert_eq!(byte_offset(line, 10), line.len());
+    }
+
+    /// Complete `text` at `character` of its first line, return the labels and
+    /// the start of the replaced range
+    fn complete(text: &str, character: u32) -> Vec<(String, u32)> {
+        let uri = Url::parse("file:///tmp/test.profile").unwrap();
+        let params: CompletionParams = serde_json::from_value(json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": character },
+        }))
+        .unwrap();
+        let documents = HashMap::from([(uri, text.to_string())]);
+        match completion(&params, &documents) {
+            Some(CompletionResponse::Array(items)) => items
+                .into_iter()
+                .map(|item| match item.text_edit {
+                    Some(CompletionTextEdit::Edit(edit)) => {
+                        (item.label, edit.range.start.character)
+                    }
+                    _ => panic!("completion without text edit"),
+                })
+                .collect(),
+            _ => panic!("no completion"),
+        }
+    }
+
+    #[test]
+    fn complete_command() {
+        assert_eq!(complete("noro", 4), [("noroot".to_string(), 0)]);
+        assert_eq!(
+            complete("ignore net", 10),
+            [("netfilter".to_string(), 7), ("net none".to_string(), 7),]
+        );
+        assert_eq!(
+            complete("?HAS_X11: x11", 13),
+            [("x11".to_string(), 10), ("x11 none".to_string(), 10)]
+        );
+    }
+
+    #[test]
+    fn complete_condition() {
+        let conditions = complete("?HAS", 4);
+        assert_eq!(conditions.len(), CONDITIONS.len());
+        assert!(conditions.contains(&("?HAS_X11:".to_string(), 0)));
+    }
+
+    #[test]
+    fn complete_value() {
+        assert_eq!(
+            complete("protocol unix,in", 16),
+            [("inet".to_string(), 14), ("inet6".to_string(), 14)]
+        );
+        // The position is in UTF-16 code units, not bytes.
+        assert_eq!(
+            complete("protocol 😀,in", 14),
+            [("inet".to_string(), 12), ("inet6".to_string(), 12)]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for firejail's path macros (`${HOME}`, `${DOWNLOADS}`, ...)
+
+use crate::cli::CliFormat;
+use crate::fatal;
+use crate::profile_stream::{Macro, ProfilePath};
+use crate::utils::{home_dir, join, print_json};
+use crate::SYSTEM_PROFILE_DIR;
+use log::debug;
+use nix::unistd::getuid;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// The directories `${PATH}` expands to, like firejail does
+pub const PATH_DIRS: &[&str] = &[
+    "/usr/local/sbin",
+    "/usr/local/bin",
+    "/usr/sbin",
+    "/usr/bin",
+    "/sbin",
+    "/bin",
+    "/usr/games",
+    "/usr/local/games",
+];
+
+pub fn start(cli: &crate::cli::CliMacros, format: CliFormat) {
+    debug!("subcommand: macros");
+
+    if !cli.paths.is_empty() {
+        let paths = cli
+            .paths
+            .iter()
+            .map(|path| {
+                let profile_path = path
+                    .parse::<ProfilePath>()
+                    .unwrap_or_else(|err| fatal!("{}: {}", err, path));
+                (path, expand(&profile_path))
+            })
+            .collect::<Vec<_>>();
+
+        if format == CliFormat::Json {
+            print_json(&json!({
+                "paths": paths
+                    .iter()
+                    .map(|(path, expanded)| json!({ "path": path, "expanded": expanded }))
+                    .collect::<Vec<_>>(),
+            }));
+        } else {
+            for (path, expanded) in &paths {
+                if expanded.is_empty() {
+                    println!("{}  (not set)", path);
+                }
+                for expanded_path in expanded {
+                    println!("{}  {}", path, expanded_path.display());
+                }
+            }
+        }
+        return;
+    }
+
+    let macros = Macro::VALUES
+        .iter()
+        .map(|value| value.parse::<Macro>().unwrap())
+        .map(|macro_| (macro_, resolve(macro_)))
+        .collect::<Vec<_>>();
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "macros": macros
+                .iter()
+                .map(|(macro_, values)| json!({ "macro": macro_, "values": values }))
+                .collect::<Vec<_>>(),
+        }));
+        return;
+    }
+
+    let width = Macro::VALUES.iter().map(|v| v.len()).max().unwrap_or(0);
+    for (macro_, values) in &macros {
+        println!(
+            "{:width$}  {}",
+            macro_.to_string(),
+            if values.is_empty() {
+                "(not set)".to_string()
+            } else {
+                join(':', values.iter().map(|value| value.display()))
+            },
+            width = width
+        );
+    }
+}
+
+/// Resolve `macro_` for the current user
+///
+/// Returns an empty `Vec` if the macro is not set, e.g. `${MUSIC}` without an entry in
+/// `~/.config/user-dirs.dirs`. `${PATH}` resolves to all [`PATH_DIRS`].
+pub fn resolve(macro_: Macro) -> Vec<PathBuf> {
+    let xdg_dir = |key: &str| {
+        let home = home_dir()?;
+        let data = read_to_string(home.join(".config/user-dirs.dirs")).ok()?;
+        parse_user_dirs(&data, &home).remove(key)
+    };
+
+    match macro_ {
+        // components() removes the trailing slash of /etc/firejail/.
+        Macro::Cfg => vec![SYSTEM_PROFILE_DIR.get_ref().components().collect()],
+        Macro::Desktop => xdg_dir("XDG_DESKTOP_DIR").into_iter().collect(),
+        Macro::Documents => xdg_dir("XDG_DOCUMENTS_DIR").into_iter().collect(),
+        Macro::Downloads => xdg_dir("XDG_DOWNLOAD_DIR").into_iter().collect(),
+        Macro::Home => home_dir().into_iter().collect(),
+        Macro::Music => xdg_dir("XDG_MUSIC_DIR").into_iter().collect(),
+        Macro::Path => PATH_DIRS.iter().map(PathBuf::from).collect(),
+        Macro::Pictures => xdg_dir("XDG_PICTURES_DIR").into_iter().collect(),
+        Macro::Runuser => vec![PathBuf::from(format!("/run/user/{}", getuid()))],
+        Macro::Videos => xdg_dir("XDG_VIDEOS_DIR").into_iter().collect(),
+    }
+}
+
+/// Expand all macros in `path` and a leading `~`
+///
+/// A path can expand to multiple paths (`${PATH}`) or to none if a macro is not set.
+pub fn expand(path: &ProfilePath) -> Vec<PathBuf> {
+    let raw = path.as_str();
+    let mut expanded = vec![match raw.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("${{HOME}}{}", rest),
+        _ => raw.to_string(),
+    }];
+
+    let mut macros = path.macros().collect::<Vec<_>>();
+    if expanded[0] != raw {
+        macros.push(Macro::Home);
+    }
+    macros.sort_unstable();
+    macros.dedup();
+
+    for macro_ in macros {
+        let values = resolve(macro_);
+        let pattern = &macro_.to_string();
+        expanded = expanded
+            .iter()
+            .flat_map(|path| {
+                values
+                    .iter()
+                    .map(move |value| path.replace(pattern, &value.to_string_lossy()))
+            })
+            .collect();
+    }
+
+    expanded.into_iter().map(PathBuf::from).collect()
+}
+
+/// Parse a `user-dirs.dirs` file into a map from `XDG_*_DIR` to the path
+fn parse_user_dirs(data: &str, home: &Path) -> HashMap<String, PathBuf> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim_matches('"');
+            let path = match value.strip_prefix("$HOME") {
+                Some(rest) => home.join(rest.trim_start_matches('/')),
+                None => PathBuf::from(value),
+            };
+            (key.to_string(), path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_user_dirs() {
+        let user_dirs = parse_user_dirs(
+            "# written by xdg-user-dirs-update\nXDG_DOWNLOAD_DIR=\"$HOME/Downloads\"\nXDG_MUSIC_DIR=\"/data/music\"\n",
+            Path::new("/home/user"),
+        );
+        assert_eq!(
+            user_dirs.get("XDG_DOWNLOAD_DIR"),
+            Some(&PathBuf::from("/home/user/Downloads"))
+        );
+        assert_eq!(
+            user_dirs.get("XDG_MUSIC_DIR"),
+            Some(&PathBuf::from("/data/music"))
+        );
+        assert_eq!(user_dirs.get("XDG_VIDEOS_DIR"), None);
+    }
+}
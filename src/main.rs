@@ -25,70 +25,211 @@
 use clap::Parser;
 use env_logger::{Builder, Env};
 use lazy_static::lazy_static;
-use log::warn;
+use log::{debug, warn};
 use nix::unistd::getuid;
+use std::env::{args_os, var_os};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
 
 mod cli;
+mod conditions;
+mod config;
+mod effective;
+mod firejail_config;
 mod location;
 mod profile;
 mod profile_stream;
 mod utils;
 
+use config::CONFIG;
 use location::Location;
-use utils::home_dir;
+use utils::{home_dir, join};
 
+mod apply;
 mod cat;
+mod config_status;
+mod coverage;
 mod diff;
 mod disable;
 mod edit;
 mod enable;
+mod export;
+mod external;
+mod firecfg;
+mod fmt;
 mod generate_standalone;
 mod has;
+mod import;
 mod list;
+mod lsp;
+mod macros;
+mod new;
+mod overlay;
+mod paths;
+mod policy;
 mod rm;
+mod shortnames;
+mod validate;
+mod which;
 
+use apply::start as start_apply;
 use cat::start as start_cat;
+use config_status::start as start_config_status;
+use coverage::start as start_coverage;
 use diff::start as start_diff;
 use disable::start as start_disable;
 use edit::start as start_edit;
 use enable::start as start_enable;
+use export::start as start_export;
+use external::start as start_external;
+use firecfg::start as start_firecfg;
+use fmt::start as start_fmt;
 use generate_standalone::start as start_generate_standalone;
 use has::start as start_has;
+use import::start as start_import;
 use list::start as start_list;
+use lsp::start as start_lsp;
+use macros::start as start_macros;
+use new::start as start_new;
+use overlay::start as start_overlay;
+use paths::start as start_paths;
+use policy::start as start_policy;
 use rm::start as start_rm;
+use shortnames::start as start_shortnames;
+use validate::start as start_validate;
+use which::start as start_which;
 
 lazy_static! {
-    static ref SYSTEM_PROFILE_DIR: Location = Location::from("/etc/firejail/");
-    static ref USER_PROFILE_DIR: Location = {
-        Location::from(
-            home_dir()
-                .expect("Can not get User's home dir.")
-                .join(".config/firejail/"),
-        )
-    };
+    /// `$FJP_SYSTEM_PROFILE_DIR`, `profile-dirs.system` from the config or `/etc/firejail/`
+    static ref SYSTEM_PROFILE_DIR: Location = var_os("FJP_SYSTEM_PROFILE_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(Location::from)
+        .or_else(|| CONFIG.profile_dirs.system.as_ref().map(Location::from))
+        .unwrap_or_else(|| Location::from("/etc/firejail/"));
+    /// `$FJP_USER_PROFILE_DIR`, `profile-dirs.user` from the config or `~/.config/firejail/`
+    static ref USER_PROFILE_DIR: Location = var_os("FJP_USER_PROFILE_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(Location::from)
+        .or_else(|| CONFIG.user_profile_dir().map(Location::from))
+        .unwrap_or_else(|| {
+            Location::from(
+                home_dir()
+                    .expect("Can not get User's home dir.")
+                    .join(".config/firejail/"),
+            )
+        });
+    /// The `--profile-dir`s, set by `main` before any profile is looked up
+    static ref CLI_PROFILE_DIRS: Mutex<Vec<PathBuf>> = Mutex::default();
+    /// The additional profile dirs searched between `USER_PROFILE_DIR` and
+    /// `SYSTEM_PROFILE_DIR`: `--profile-dir`s and `$FJP_PROFILE_PATH` followed by
+    /// `profile-dirs.extra` from the config
+    static ref EXTRA_PROFILE_DIRS: Vec<Location> = Location::extra_dirs(
+        &CLI_PROFILE_DIRS.lock().unwrap(),
+        var_os("FJP_PROFILE_PATH").as_deref(),
+        CONFIG.extra_profile_dirs(),
+    );
 }
 
 fn main() {
     #[cfg(feature = "full")]
     color_backtrace::install();
 
-    Builder::from_env(Env::new().default_filter_or("info"))
-        .format_timestamp(None)
-        .init();
+    let mut cli = cli::Cli::parse();
+
+    let mut logger = Builder::from_env(Env::new().default_filter_or("info"));
+    logger.format_timestamp(None);
+    if cli.output == cli::CliFormat::Json {
+        logger.format(|buf, record| {
+            writeln!(
+                buf,
+                "{}",
+                serde_json::json!({
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                })
+            )
+        });
+    }
+    logger.init();
+
+    // Load the config now, so errors in it are reported before any output.
+    lazy_static::initialize(&CONFIG);
+
+    if let cli::Subcommands::External(args) = &cli.subcommand {
+        if let Some(args) = expand_alias(args) {
+            cli = cli::Cli::parse_from(args);
+        }
+    }
+
+    // External subcommands get them as FJP_PROFILE_PATH, see external::start.
+    *CLI_PROFILE_DIRS.lock().unwrap() = cli.profile_dirs.clone();
 
     if getuid().is_root() {
         warn!("fjp is designed to be used as regular user.");
     }
 
-    match &cli::Cli::parse().subcommand {
-        cli::Subcommands::Cat(cli) => start_cat(cli),
-        cli::Subcommands::Diff(cli) => start_diff(cli),
-        cli::Subcommands::Disable(cli) => start_disable(cli),
-        cli::Subcommands::Edit(cli) => start_edit(cli),
-        cli::Subcommands::Enable(cli) => start_enable(cli),
-        cli::Subcommands::GenerateStandalone(cli) => start_generate_standalone(cli),
-        cli::Subcommands::Has(cli) => start_has(cli),
-        cli::Subcommands::List(cli) => start_list(cli),
-        cli::Subcommands::Rm(cli) => start_rm(cli),
+    match &cli.subcommand {
+        cli::Subcommands::Apply(sub_cli) => start_apply(sub_cli, cli.output),
+        cli::Subcommands::Cat(sub_cli) => start_cat(sub_cli, cli.output),
+        cli::Subcommands::ConfigStatus(sub_cli) => start_config_status(sub_cli, cli.output),
+        cli::Subcommands::Coverage(sub_cli) => start_coverage(sub_cli, cli.output),
+        cli::Subcommands::Diff(sub_cli) => start_diff(sub_cli, cli.output),
+        cli::Subcommands::Disable(sub_cli) => start_disable(sub_cli, cli.output),
+        cli::Subcommands::Edit(sub_cli) => start_edit(sub_cli),
+        cli::Subcommands::Enable(sub_cli) => start_enable(sub_cli),
+        cli::Subcommands::Export(sub_cli) => start_export(sub_cli),
+        cli::Subcommands::Firecfg(sub_cli) => start_firecfg(sub_cli, cli.output),
+        cli::Subcommands::Fmt(sub_cli) => start_fmt(sub_cli, cli.output),
+        cli::Subcommands::GenerateStandalone(sub_cli) => start_generate_standalone(sub_cli),
+        cli::Subcommands::Has(sub_cli) => start_has(sub_cli, cli.output),
+        cli::Subcommands::Import(sub_cli) => start_import(sub_cli),
+        cli::Subcommands::List(sub_cli) => start_list(sub_cli, cli.output),
+        cli::Subcommands::Lsp(sub_cli) => start_lsp(sub_cli),
+        cli::Subcommands::Macros(sub_cli) => start_macros(sub_cli, cli.output),
+        cli::Subcommands::New(sub_cli) => start_new(sub_cli),
+        cli::Subcommands::Overlay(sub_cli) => start_overlay(sub_cli, cli.output),
+        cli::Subcommands::Paths(sub_cli) => start_paths(sub_cli, cli.output),
+        cli::Subcommands::Policy(sub_cli) => start_policy(sub_cli, cli.output),
+        cli::Subcommands::Rm(sub_cli) => start_rm(sub_cli),
+        cli::Subcommands::Shortnames(sub_cli) => start_shortnames(sub_cli, cli.output),
+        cli::Subcommands::Validate(sub_cli) => start_validate(sub_cli, cli.output),
+        cli::Subcommands::Which(sub_cli) => start_which(sub_cli, cli.output),
+        cli::Subcommands::External(args) => start_external(args, cli.output),
     }
 }
+
+/// Expand the alias `args[0]` from the config file
+///
+/// Returns the new command line or `None` if `args[0]` is not an alias.
+/// Aliases can use other aliases, but must not be recursive.
+fn expand_alias(args: &[String]) -> Option<Vec<OsString>> {
+    let mut expanded = args.to_vec();
+    let mut seen = Vec::new();
+    while let Some(expansion) = CONFIG.alias(&expanded[0]) {
+        if seen.contains(&expanded[0]) {
+            fatal!("Alias '{}' is recursive.", args[0]);
+        }
+        if expansion.is_empty() {
+            fatal!("Alias '{}' is empty.", expanded[0]);
+        }
+        debug!(
+            "Expanded alias '{}' to '{}'.",
+            expanded[0],
+            join(' ', &expansion)
+        );
+        seen.push(expanded.remove(0));
+        expanded.splice(0..0, expansion);
+    }
+    if seen.is_empty() {
+        return None;
+    }
+
+    // Keep everything before the alias, e.g. `--output json`.
+    let mut argv = args_os().collect::<Vec<_>>();
+    argv.truncate(argv.len() - args.len());
+    argv.extend(expanded.into_iter().map(OsString::from));
+    Some(argv)
+}
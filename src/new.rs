@@ -0,0 +1,311 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for creating new profiles from the template
+
+use crate::cli::CliInterpreter;
+use crate::effective::LOOKUP_FLAGS;
+use crate::fatal;
+use crate::profile::{Profile, ProfileFlags};
+use crate::USER_PROFILE_DIR;
+use log::{debug, info, warn};
+use std::fs::{create_dir_all, write};
+
+/// The choices for the template
+#[derive(Debug, Default)]
+pub struct Template<'a> {
+    pub gui: bool,
+    pub no_network: bool,
+    pub interpreters: &'a [CliInterpreter],
+    /// Create a redirect profile which includes this `.profile`
+    pub redirect: Option<&'a str>,
+}
+
+pub fn start(cli: &crate::cli::CliNew) {
+    debug!("subcommand: new");
+
+    let name = cli.name.strip_suffix(".profile").unwrap_or(&cli.name);
+    if name.is_empty() || name.contains('/') {
+        fatal!("'{}' is not a valid profile name.", cli.name);
+    }
+
+    let target = cli.target.as_deref().map(|target| {
+        let target_name = target.strip_suffix(".profile").unwrap_or(target);
+        if target_name.is_empty() || target_name.contains('/') {
+            fatal!("'{}' is not a valid profile name.", target);
+        }
+        if target_name == name {
+            fatal!("{}.profile can not redirect to itself.", name);
+        }
+        format!("{}.profile", target_name)
+    });
+    let path = USER_PROFILE_DIR.get_profile_path(&format!("{}.profile", name));
+    if path.exists() && !cli.force {
+        fatal!(
+            "{} already exists, use fjp edit or --force to overwrite it.",
+            path.display()
+        );
+    }
+
+    if let Some(target) = &target {
+        if Profile::new(target, LOOKUP_FLAGS.without(ProfileFlags::READ))
+            .map_or(true, |profile| profile.path().is_none())
+        {
+            warn!("{} does not exist.", target);
+        }
+    }
+
+    let profile = template(
+        name,
+        &Template {
+            gui: cli.gui,
+            no_network: cli.no_network,
+            interpreters: &cli.interpreter,
+            redirect: target.as_deref(),
+        },
+    );
+    if let Err(err) = create_dir_all(USER_PROFILE_DIR.get_ref()) {
+        fatal!("Failed to create the user profile directory: {}", err);
+    }
+    write(&path, profile)
+        .unwrap_or_else(|err| fatal!("Failed to write {}: {}", path.display(), err));
+    info!("Created {}", path.display());
+}
+
+/// Generate a profile for the program `name` in the order of profile.template
+pub fn template(name: &str, options: &Template<'_>) -> String {
+    if let Some(target) = options.redirect {
+        return redirect_template(name, target, options);
+    }
+
+    let mut profile = format!(
+        "# Firejail profile for {name}\n\
+         # Persistent local customizations\n\
+         include {name}.local\n\
+         # Persistent global definitions\n\
+         include globals.local\n\n",
+        name = name,
+    );
+    profile += &allow_interpreters(options.interpreters);
+
+    profile += &format!(
+        "#noblacklist ${{HOME}}/.config/{name}\n\
+         include disable-common.inc\n\
+         include disable-devel.inc\n\
+         include disable-exec.inc\n\
+         include disable-interpreters.inc\n\
+         include disable-programs.inc\n\
+         include disable-shell.inc\n\
+         include disable-xdg.inc\n\n\
+         #mkdir ${{HOME}}/.config/{name}\n\
+         #whitelist ${{HOME}}/.config/{name}\n\
+         include whitelist-common.inc\n\
+         include whitelist-runuser-common.inc\n\
+         include whitelist-usr-share-common.inc\n\
+         include whitelist-var-common.inc\n\n",
+        name = name,
+    );
+
+    let mut options_block = vec!["apparmor", "caps.drop all", "ipc-namespace", "machine-id"];
+    options_block.push(if options.no_network {
+        "net none"
+    } else {
+        "netfilter"
+    });
+    if !options.gui {
+        options_block.push("no3d");
+    }
+    options_block.extend(["nodvd", "nogroups", "noinput", "nonewprivs", "noroot"]);
+    if !options.gui {
+        options_block.push("nosound");
+    }
+    options_block.extend(["notv", "nou2f", "novideo"]);
+    options_block.push(if options.no_network {
+        "protocol unix"
+    } else {
+        "protocol unix,inet,inet6"
+    });
+    options_block.extend(["seccomp", "seccomp.block-secondary", "tracelog"]);
+    profile += &options_block.join("\n");
+    profile += "\n\n";
+
+    let mut bins = vec![name];
+    bins.extend(
+        options
+            .interpreters
+            .iter()
+            .map(|&interpreter| interpreter_files(interpreter).1),
+    );
+    profile += &format!(
+        "disable-mnt\n\
+         private-bin {}\n\
+         private-cache\n\
+         private-dev\n\
+         #private-etc FILES\n\
+         private-tmp\n\n\
+         dbus-user none\n\
+         dbus-system none\n\n",
+        bins.join(","),
+    );
+    // JIT compilers (java, node and most GUI toolkits) need writable and executable memory
+    if options.gui
+        || options
+            .interpreters
+            .iter()
+            .any(|interpreter| matches!(interpreter, CliInterpreter::Java | CliInterpreter::Nodejs))
+    {
+        profile += "#memory-deny-write-execute\n";
+    } else {
+        profile += "memory-deny-write-execute\n";
+    }
+
+    profile
+}
+
+/// Generate a redirect profile for the program `name` which includes `target`
+///
+/// globals.local is included by `target` already, like in the upstream redirect profiles.
+fn redirect_template(name: &str, target: &str, options: &Template<'_>) -> String {
+    let mut profile = format!(
+        "# Firejail profile for {name}\n\
+         # Persistent local customizations\n\
+         include {name}.local\n\n\
+         # Persistent global definitions\n\
+         # added by included profile\n\
+         #include globals.local\n\n",
+        name = name,
+    );
+    profile += &allow_interpreters(options.interpreters);
+
+    profile += &format!(
+        "#noblacklist ${{HOME}}/.config/{name}\n\
+         #mkdir ${{HOME}}/.config/{name}\n\
+         #whitelist ${{HOME}}/.config/{name}\n",
+        name = name,
+    );
+    if options.no_network {
+        profile += "net none\n\n";
+    } else {
+        profile += "\n";
+    }
+
+    profile += &format!("# Redirect\ninclude {}\n", target);
+    profile
+}
+
+/// The includes of the allow-*.inc of `interpreters` followed by a blank line
+fn allow_interpreters(interpreters: &[CliInterpreter]) -> String {
+    let mut lines = String::new();
+    if interpreters.is_empty() {
+        return lines;
+    }
+    for &interpreter in interpreters {
+        let (inc, _) = interpreter_files(interpreter);
+        lines += &format!(
+            "# Allow {} (blacklisted by disable-interpreters.inc)\ninclude {}\n",
+            inc.trim_start_matches("allow-").trim_end_matches(".inc"),
+            inc
+        );
+    }
+    lines += "\n";
+    lines
+}
+
+/// The allow-*.inc and the private-bin entry of `interpreter`
+fn interpreter_files(interpreter: CliInterpreter) -> (&'static str, &'static str) {
+    match interpreter {
+        CliInterpreter::Java => ("allow-java.inc", "java"),
+        CliInterpreter::Lua => ("allow-lua.inc", "lua*"),
+        CliInterpreter::Nodejs => ("allow-nodejs.inc", "node"),
+        CliInterpreter::Perl => ("allow-perl.inc", "perl"),
+        CliInterpreter::Php => ("allow-php.inc", "php*"),
+        CliInterpreter::Python2 => ("allow-python2.inc", "python2*"),
+        CliInterpreter::Python3 => ("allow-python3.inc", "python3*"),
+        CliInterpreter::Ruby => ("allow-ruby.inc", "ruby"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fmt::format_profile;
+    use crate::profile_stream::ProfileStream;
+
+    #[test]
+    fn template_is_valid_and_formatted() {
+        for options in [
+            Template::default(),
+            Template {
+                gui: true,
+                no_network: true,
+                interpreters: &[CliInterpreter::Python3, CliInterpreter::Perl],
+                redirect: None,
+            },
+            Template {
+                redirect: Some("foo-common.profile"),
+                ..Template::default()
+            },
+            Template {
+                no_network: true,
+                interpreters: &[CliInterpreter::Python3],
+                redirect: Some("foo-common.profile"),
+                ..Template::default()
+            },
+        ] {
+            let profile = template("foo", &options);
+            let profile_stream = profile.parse::<ProfileStream>().unwrap();
+            assert_eq!(format_profile(&profile_stream).to_string(), profile);
+        }
+    }
+
+    #[test]
+    fn template_comments_out_mdwe_for_jit() {
+        let mdwe = |options: &Template<'_>| {
+            template("foo", options)
+                .lines()
+                .find(|line| line.contains("memory-deny-write-execute"))
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(mdwe(&Template::default()), "memory-deny-write-execute");
+        assert_eq!(
+            mdwe(&Template {
+                interpreters: &[CliInterpreter::Python3],
+                ..Template::default()
+            }),
+            "memory-deny-write-execute"
+        );
+        for options in [
+            Template {
+                gui: true,
+                ..Template::default()
+            },
+            Template {
+                interpreters: &[CliInterpreter::Java],
+                ..Template::default()
+            },
+            Template {
+                interpreters: &[CliInterpreter::Perl, CliInterpreter::Nodejs],
+                ..Template::default()
+            },
+        ] {
+            assert_eq!(mdwe(&options), "#memory-deny-write-execute");
+        }
+    }
+}
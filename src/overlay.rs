@@ -0,0 +1,528 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for the team overlay, a directory with shared profiles installed into
+//! `USER_PROFILE_DIR`
+//!
+//! In copy mode, the installed version of every overlay file is stored in `BASE_DIR`.
+//! This allows to distinguish between files which are out of date (the overlay changed)
+//! and files which are locally modified (the user changed them).
+//! Local modifications are moved into `<NAME>.personal` on apply, which is included
+//! at the top of the installed file. Lines the user removed become `ignore` lines there,
+//! like in a `.local`.
+
+use crate::cli::{CliFormat, CliOverlayApply, CliOverlayStatus, OverlaySubcommands};
+use crate::config::{OverlayMode, CONFIG};
+use crate::list::read_names;
+use crate::location::Location;
+use crate::profile_stream::Command;
+use crate::utils::{input, print_json};
+use crate::{fatal, USER_PROFILE_DIR};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::fs::{
+    canonicalize, create_dir_all, read_link, read_to_string, remove_file, symlink_metadata, write,
+};
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+/// The directory in `USER_PROFILE_DIR` with the installed versions of the overlay files
+const BASE_DIR: &str = ".overlay";
+
+/// The comment above the include of the personal file
+const PERSONAL_COMMENT: &str = "# Personal changes, added by `fjp overlay apply`.";
+
+/// The status of a file from the overlay in `USER_PROFILE_DIR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Status {
+    /// Not installed yet
+    Missing,
+    /// Installed and up to date
+    Installed,
+    /// Installed as symlink to the overlay
+    Linked,
+    /// Installed and not modified, but the overlay changed
+    Outdated,
+    /// Installed and modified, the overlay did not change
+    Modified,
+    /// Installed and modified, and the overlay changed
+    Conflict,
+    /// A file with the same name exists, but it was not installed by fjp
+    Untracked,
+}
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Missing => "missing",
+            Self::Installed => "installed",
+            Self::Linked => "linked",
+            Self::Outdated => "outdated",
+            Self::Modified => "modified",
+            Self::Conflict => "conflict",
+            Self::Untracked => "untracked",
+        }
+    }
+}
+
+/// A file from the overlay
+#[derive(Debug)]
+struct OverlayFile {
+    name: String,
+    /// The path of the file in the overlay
+    path: PathBuf,
+    /// The path of the file in the user profile dir
+    user_path: PathBuf,
+    /// The path of the installed version in `BASE_DIR`
+    base_path: PathBuf,
+    /// The path of the file with the personal changes in the user profile dir
+    personal_path: PathBuf,
+}
+impl OverlayFile {
+    fn new(overlay: &Location, user_dir: &Location, name: String) -> Self {
+        let personal_name = personal_name(&name);
+        Self {
+            path: overlay.get_profile_path(&name),
+            user_path: user_dir.get_profile_path(&name),
+            base_path: user_dir.get_profile_path(BASE_DIR).join(&name),
+            personal_path: user_dir.get_profile_path(&personal_name),
+            name,
+        }
+    }
+
+    /// The content a installed copy of this file should have
+    ///
+    /// The personal file is included first, so its `ignore` lines apply to the whole file.
+    fn expected_content(&self) -> io::Result<String> {
+        let content = read_to_string(&self.path)?;
+        if self.personal_path.exists() {
+            Ok(format!(
+                "{}\ninclude {}\n\n{}",
+                PERSONAL_COMMENT,
+                personal_name(&self.name),
+                content
+            ))
+        } else {
+            Ok(content)
+        }
+    }
+
+    fn status(&self) -> io::Result<Status> {
+        match symlink_metadata(&self.user_path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if read_link(&self.user_path)? == self.path {
+                    return Ok(Status::Linked);
+                }
+            }
+            Ok(_) => (),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Status::Missing),
+            Err(err) => return Err(err),
+        }
+
+        let user_content = read_to_string(&self.user_path)?;
+        let expected_content = self.expected_content()?;
+        let base_content = match read_to_string(&self.base_path) {
+            Ok(base_content) => base_content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(if user_content == expected_content {
+                    Status::Installed
+                } else {
+                    Status::Untracked
+                });
+            }
+            Err(err) => return Err(err),
+        };
+
+        Ok(
+            match (
+                user_content == base_content,
+                base_content == expected_content,
+            ) {
+                (true, true) => Status::Installed,
+                (true, false) => Status::Outdated,
+                (false, true) => Status::Modified,
+                (false, false) => Status::Conflict,
+            },
+        )
+    }
+}
+
+/// The name of the file with the personal changes for `name`
+fn personal_name(name: &str) -> String {
+    format!("{}.personal", name)
+}
+
+pub fn start(cli: &crate::cli::CliOverlay, format: CliFormat) {
+    debug!("subcommand: overlay");
+
+    match &cli.subcommand {
+        OverlaySubcommands::Status(sub_cli) => status(sub_cli, format),
+        OverlaySubcommands::Apply(sub_cli) => apply(sub_cli),
+    }
+}
+
+/// Return the absolute overlay dir from `--overlay` or the config file
+fn overlay_dir(overlay: Option<&Path>) -> Location {
+    let overlay = overlay
+        .map(Path::to_path_buf)
+        .or_else(|| CONFIG.overlay_dir())
+        .unwrap_or_else(|| {
+            fatal!("No overlay directory given, use --overlay or set overlay.dir in the config.")
+        });
+    // Absolute, because symlinks to it must be valid from USER_PROFILE_DIR.
+    canonicalize(&overlay)
+        .map(Location::from)
+        .unwrap_or_else(|err| {
+            fatal!(
+                "Failed to open the overlay '{}': {}",
+                overlay.display(),
+                err
+            )
+        })
+}
+
+/// Read all `.inc`, `.local` and `.profile` files in `overlay`
+fn read_overlay(overlay: &Location, user_dir: &Location) -> io::Result<Vec<OverlayFile>> {
+    Ok(read_names(overlay)?
+        .into_iter()
+        .filter(|name| {
+            name.ends_with(".inc") || name.ends_with(".local") || name.ends_with(".profile")
+        })
+        .map(|name| OverlayFile::new(overlay, user_dir, name))
+        .collect())
+}
+
+fn status(cli: &CliOverlayStatus, format: CliFormat) {
+    let overlay = overlay_dir(cli.overlay.as_deref());
+    let files = read_overlay(&overlay, &USER_PROFILE_DIR)
+        .unwrap_or_else(|err| fatal!("Failed to open the overlay '{}': {}", overlay, err))
+        .into_iter()
+        .filter_map(|file| match file.status() {
+            Ok(status) => Some((file.name, status)),
+            Err(err) => {
+                error!("Failed to get the status of '{}': {}", file.name, err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "overlay": overlay.get_ref(),
+            "files": files
+                .iter()
+                .map(|(name, status)| json!({ "name": name, "status": status }))
+                .collect::<Vec<_>>(),
+        }));
+        return;
+    }
+
+    let width = files.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, status) in files {
+        println!("{:width$}  {}", name, status.as_str(), width = width);
+    }
+}
+
+fn apply(cli: &CliOverlayApply) {
+    let overlay = overlay_dir(cli.overlay.as_deref());
+    let mode = if cli.symlink {
+        OverlayMode::Symlink
+    } else {
+        CONFIG.overlay.mode
+    };
+
+    let files = read_overlay(&overlay, &USER_PROFILE_DIR)
+        .unwrap_or_else(|err| fatal!("Failed to open the overlay '{}': {}", overlay, err));
+    for file in files {
+        let replace_untracked = || {
+            warn!(
+                "{} exists but was not installed by fjp overlay.",
+                file.user_path.display()
+            );
+            input(&format!(
+                "Replace it? Your lines are kept in {}. [y/N] ",
+                personal_name(&file.name)
+            ))
+            .map_or(false, |answer| answer.to_lowercase() == "y")
+        };
+        if let Err(err) = apply_file(&file, mode, replace_untracked) {
+            error!("Failed to apply '{}': {}", file.name, err);
+        }
+    }
+}
+
+/// Install `file` in `mode`
+///
+/// `replace_untracked` is asked before a file which was not installed by fjp is replaced.
+fn apply_file<F: FnOnce() -> bool>(
+    file: &OverlayFile,
+    mode: OverlayMode,
+    replace_untracked: F,
+) -> io::Result<()> {
+    let status = file.status()?;
+    debug!("{}: {}", file.name, status.as_str());
+
+    if status == Status::Untracked && !replace_untracked() {
+        info!("Skipping {}.", file.name);
+        return Ok(());
+    }
+    if matches!(
+        status,
+        Status::Modified | Status::Conflict | Status::Untracked
+    ) {
+        save_personal_changes(file, status)?;
+    }
+
+    // A symlink can not include the personal changes, so such files are copied.
+    let mode = if mode == OverlayMode::Symlink && file.personal_path.exists() {
+        info!(
+            "Installing a copy of {} to include {}.",
+            file.name,
+            personal_name(&file.name)
+        );
+        OverlayMode::Copy
+    } else {
+        mode
+    };
+
+    if matches!(
+        (status, mode),
+        (Status::Installed, OverlayMode::Copy) | (Status::Linked, OverlayMode::Symlink)
+    ) {
+        return Ok(());
+    }
+
+    if symlink_metadata(&file.user_path).is_ok() {
+        remove_file(&file.user_path)?;
+    }
+
+    match mode {
+        OverlayMode::Copy => {
+            let content = file.expected_content()?;
+            write(&file.user_path, &content)?;
+            create_dir_all(file.base_path.parent().unwrap())?;
+            write(&file.base_path, &content)?;
+        }
+        OverlayMode::Symlink => {
+            symlink(&file.path, &file.user_path)?;
+            match remove_file(&file.base_path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+        }
+    }
+
+    info!("Applied {} ({}).", file.name, status.as_str());
+    Ok(())
+}
+
+/// Move the changes the user made to `file` into its personal file
+///
+/// Added lines are appended, removed commands are appended as `ignore` lines.
+fn save_personal_changes(file: &OverlayFile, status: Status) -> io::Result<()> {
+    let user_content = read_to_string(&file.user_path)?;
+    // An untracked file was not derived from the overlay, so nothing was removed from it.
+    let untracked = status == Status::Untracked;
+    let base_content = if untracked {
+        read_to_string(&file.path)?
+    } else {
+        read_to_string(&file.base_path)?
+    };
+    let mut personal_content = match read_to_string(&file.personal_path) {
+        Ok(personal_content) => personal_content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let personal_include = format!("include {}", personal_name(&file.name));
+    let is_own_line = |line: &str| {
+        line.trim().is_empty() || line == PERSONAL_COMMENT || line.trim() == personal_include
+    };
+
+    let added_lines = user_content
+        .lines()
+        .filter(|line| !is_own_line(line))
+        .filter(|line| !base_content.lines().any(|l| l == *line))
+        .map(str::to_string);
+    let removed_lines = base_content
+        .lines()
+        .filter(|line| !untracked && !is_own_line(line))
+        .filter(|line| !user_content.lines().any(|l| l == *line))
+        .filter_map(|line| match line.trim().parse::<Command>() {
+            Ok(Command::Ignore(_)) | Err(_) => {
+                if !line.trim_start().starts_with('#') {
+                    warn!(
+                        "Can not keep the removal of '{}' from {}, it can not be ignored.",
+                        line, file.name
+                    );
+                }
+                None
+            }
+            Ok(command) => Some(format!("ignore {}", command)),
+        });
+    let new_lines = added_lines
+        .chain(removed_lines)
+        .filter(|line| !personal_content.lines().any(|l| l == line))
+        .collect::<Vec<_>>();
+    if new_lines.is_empty() {
+        return Ok(());
+    }
+
+    if !personal_content.is_empty() && !personal_content.ends_with('\n') {
+        personal_content.push('\n');
+    }
+    for line in new_lines {
+        personal_content.push_str(&line);
+        personal_content.push('\n');
+    }
+    debug!(
+        "Save personal changes to '{}'",
+        file.personal_path.display()
+    );
+    write(&file.personal_path, personal_content)?;
+    info!(
+        "Moved your changes of {} to {}.",
+        file.name,
+        personal_name(&file.name)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    const BASE: &str = "include foo.local\n\nnoroot\nnogroups\n";
+
+    /// An overlay with `foo.profile` and an empty user dir
+    fn setup() -> (TempDir, OverlayFile) {
+        let tmp = TempDir::new();
+        tmp.write("overlay/foo.profile", BASE);
+        create_dir_all(tmp.path().join("user")).unwrap();
+        let file = OverlayFile::new(
+            &Location::from(tmp.path().join("overlay")),
+            &Location::from(tmp.path().join("user")),
+            "foo.profile".to_string(),
+        );
+        (tmp, file)
+    }
+
+    fn apply_copy(file: &OverlayFile) {
+        apply_file(file, OverlayMode::Copy, || panic!("asked to replace")).unwrap();
+    }
+
+    #[test]
+    fn status_clean() {
+        let (_tmp, file) = setup();
+        assert_eq!(file.status().unwrap(), Status::Missing);
+        apply_copy(&file);
+        assert_eq!(file.status().unwrap(), Status::Installed);
+        assert_eq!(read_to_string(&file.user_path).unwrap(), BASE);
+        assert_eq!(read_to_string(&file.base_path).unwrap(), BASE);
+    }
+
+    #[test]
+    fn status_modified_and_upstream_changed() {
+        let (_tmp, file) = setup();
+        apply_copy(&file);
+
+        write(&file.path, format!("{}nonewprivs\n", BASE)).unwrap();
+        assert_eq!(file.status().unwrap(), Status::Outdated);
+
+        write(&file.user_path, "include foo.local\n\nnoroot\nnosound\n").unwrap();
+        assert_eq!(file.status().unwrap(), Status::Conflict);
+
+        write(&file.path, BASE).unwrap();
+        assert_eq!(file.status().unwrap(), Status::Modified);
+    }
+
+    #[test]
+    fn status_untracked() {
+        let (_tmp, file) = setup();
+        write(&file.user_path, "noroot\n").unwrap();
+        assert_eq!(file.status().unwrap(), Status::Untracked);
+        write(&file.user_path, BASE).unwrap();
+        assert_eq!(file.status().unwrap(), Status::Installed);
+    }
+
+    #[test]
+    fn apply_moves_added_and_removed_lines() {
+        let (_tmp, file) = setup();
+        apply_copy(&file);
+        write(&file.user_path, "include foo.local\n\nnoroot\nnosound\n").unwrap();
+
+        apply_copy(&file);
+        assert_eq!(
+            read_to_string(&file.personal_path).unwrap(),
+            "nosound\nignore nogroups\n"
+        );
+        let installed = read_to_string(&file.user_path).unwrap();
+        assert!(installed.starts_with(&format!(
+            "{}\ninclude foo.profile.personal\n\n",
+            PERSONAL_COMMENT
+        )));
+        assert!(installed.ends_with(BASE));
+        assert_eq!(file.status().unwrap(), Status::Installed);
+
+        // Applying again does not duplicate the personal changes.
+        write(&file.path, format!("{}nonewprivs\n", BASE)).unwrap();
+        assert_eq!(file.status().unwrap(), Status::Outdated);
+        apply_copy(&file);
+        assert_eq!(
+            read_to_string(&file.personal_path).unwrap(),
+            "nosound\nignore nogroups\n"
+        );
+        assert_eq!(file.status().unwrap(), Status::Installed);
+    }
+
+    #[test]
+    fn apply_untracked_needs_confirmation() {
+        let (_tmp, file) = setup();
+        write(&file.user_path, "noroot\nnosound\n").unwrap();
+
+        apply_file(&file, OverlayMode::Copy, || false).unwrap();
+        assert_eq!(
+            read_to_string(&file.user_path).unwrap(),
+            "noroot\nnosound\n"
+        );
+        assert!(!file.personal_path.exists());
+
+        apply_file(&file, OverlayMode::Copy, || true).unwrap();
+        assert_eq!(read_to_string(&file.personal_path).unwrap(), "nosound\n");
+        assert_eq!(file.status().unwrap(), Status::Installed);
+    }
+
+    #[test]
+    fn apply_symlink() {
+        let (_tmp, file) = setup();
+        apply_file(&file, OverlayMode::Symlink, || panic!("asked to replace")).unwrap();
+        assert_eq!(file.status().unwrap(), Status::Linked);
+
+        // A personal file can not be included by a symlink, so it becomes a copy.
+        write(&file.personal_path, "nosound\n").unwrap();
+        apply_file(&file, OverlayMode::Symlink, || panic!("asked to replace")).unwrap();
+        assert!(!symlink_metadata(&file.user_path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(file.status().unwrap(), Status::Installed);
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for previewing the files matched by the path commands of a profile
+
+use crate::cli::{CliFormat, CliSwitch};
+use crate::conditions::Evaluator;
+use crate::effective::effective_profile;
+use crate::fatal;
+use crate::firejail_config::FirejailConfig;
+use crate::macros::expand;
+use crate::profile_stream::{Command, Condition};
+use crate::utils::{glob, print_json};
+use log::debug;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// A path of a command together with the files it matches
+#[derive(Debug, Serialize)]
+struct PathMatch {
+    location: String,
+    command: String,
+    pattern: String,
+    matches: Vec<PathBuf>,
+    /// `true` if `matches` is empty and the command does not create the path
+    unmatched: bool,
+}
+
+pub fn start(cli: &crate::cli::CliPaths, format: CliFormat) {
+    debug!("subcommand: paths");
+
+    let evaluator = Evaluator::new(
+        &FirejailConfig::load(),
+        cli.assume.iter().map(|switch| match switch {
+            CliSwitch::Appimage => Condition::HasAppimage,
+            CliSwitch::Net => Condition::HasNet,
+            CliSwitch::Nodbus => Condition::HasNodbus,
+            CliSwitch::Nosound => Condition::HasNosound,
+            CliSwitch::Private => Condition::HasPrivate,
+            CliSwitch::X11 => Condition::HasX11,
+        }),
+    );
+    let lines =
+        effective_profile(&cli.profile_name, &evaluator).unwrap_or_else(|err| fatal!("{}", err));
+
+    let path_matches = lines
+        .iter()
+        .filter(|line| line.ignored_by.is_none() && evaluator.applies(&line.line.content))
+        .filter_map(|line| line.command().map(|command| (line, command)))
+        .flat_map(|(line, command)| {
+            command.paths().into_iter().map(move |path| {
+                let mut matches = expand(path)
+                    .iter()
+                    .flat_map(|expanded| glob(expanded))
+                    .collect::<Vec<_>>();
+                matches.dedup();
+                PathMatch {
+                    location: line.location(),
+                    command: command.to_string(),
+                    pattern: path.to_string(),
+                    unmatched: matches.is_empty()
+                        && !matches!(command, Command::Mkdir(_) | Command::Mkfile(_)),
+                    matches,
+                }
+            })
+        })
+        .filter(|path_match| !cli.unmatched || path_match.unmatched)
+        .collect::<Vec<_>>();
+
+    if format == CliFormat::Json {
+        print_json(&serde_json::json!({ "paths": path_matches }));
+        return;
+    }
+
+    for path_match in &path_matches {
+        println!("{}: {}", path_match.location, path_match.command);
+        if path_match.unmatched {
+            println!("  no match for {}", path_match.pattern);
+        } else if path_match.matches.is_empty() {
+            println!("  (created by firejail)");
+        }
+        for matched in &path_match.matches {
+            println!("  {}", matched.display());
+        }
+    }
+}
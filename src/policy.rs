@@ -0,0 +1,418 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for checking profiles against a baseline policy
+//!
+//! ```toml
+//! [[rule]]
+//! id = "disable-common"
+//! require = "include disable-common.inc"
+//!
+//! [[rule]]
+//! id = "no-ssh"
+//! forbid = "noblacklist ${HOME}/.ssh"
+//! message = "~/.ssh must stay blacklisted"
+//! ```
+
+use crate::cli::{CliFormat, CliPolicyCheck, PolicySubcommands};
+use crate::conditions::Evaluator;
+use crate::effective::{effective_profile, EffectiveLine};
+use crate::list::read_names;
+use crate::profile::{complete_name, ProfileFlags};
+use crate::profile_stream::Content;
+use crate::utils::print_json;
+use crate::{fatal, SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+use std::process::exit;
+
+/// A baseline policy
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Policy {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+/// A rule of a [`Policy`], either `require` or `forbid` must be set
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    id: String,
+    /// A line which must be in every effective profile and must not be ignored
+    require: Option<String>,
+    /// A line which must not be in any effective profile
+    forbid: Option<String>,
+    /// A explanation shown for violations
+    message: Option<String>,
+}
+
+/// A violated rule
+#[derive(Debug, Serialize)]
+struct Violation {
+    profile: String,
+    rule: String,
+    message: String,
+    /// The `path:lineno` of the line causing the violation, `None` for missing lines
+    location: Option<String>,
+    /// The line causing the violation, `None` for missing lines
+    line: Option<String>,
+}
+
+pub fn start(cli: &crate::cli::CliPolicy, format: CliFormat) {
+    debug!("subcommand: policy");
+
+    match &cli.subcommand {
+        PolicySubcommands::Check(sub_cli) => check(sub_cli, format),
+    }
+}
+
+fn check(cli: &CliPolicyCheck, format: CliFormat) {
+    let policy = read_to_string(&cli.policy)
+        .unwrap_or_else(|err| fatal!("Failed to read '{}': {}", cli.policy, err));
+    let policy: Policy = toml::from_str(&policy)
+        .unwrap_or_else(|err| fatal!("Invalid policy '{}': {}", cli.policy, err));
+    let rules = policy
+        .rules
+        .iter()
+        .map(|rule| {
+            let (line, forbid) = match (&rule.require, &rule.forbid) {
+                (Some(line), None) => (line, false),
+                (None, Some(line)) => (line, true),
+                _ => fatal!("Rule '{}' needs either require or forbid.", rule.id),
+            };
+            let content = line.parse::<Content>().unwrap_or_else(|_| {
+                fatal!("Rule '{}' contains an invalid line: {}", rule.id, line)
+            });
+            (rule, content, forbid)
+        })
+        .collect::<Vec<_>>();
+
+    let profiles = if cli.profile_names.is_empty() {
+        all_profiles()
+    } else {
+        cli.profile_names
+            .iter()
+            .filter_map(
+                |name| match complete_name(name, ProfileFlags::DENY_BY_PATH) {
+                    Ok(full_name) => Some(full_name.into_owned()),
+                    Err(err) => {
+                        error!("{}", err);
+                        None
+                    }
+                },
+            )
+            .collect()
+    };
+
+    let evaluator = Evaluator::from_config();
+    let mut violations = Vec::new();
+    for profile in profiles {
+        let lines = match effective_profile(&profile, &evaluator) {
+            Ok(lines) => lines,
+            Err(err) => {
+                error!("{}", err);
+                continue;
+            }
+        };
+        for (rule, content, forbid) in &rules {
+            if *forbid {
+                check_forbid(&profile, rule, content, &lines, &evaluator, &mut violations);
+            } else {
+                check_require(&profile, rule, content, &lines, &evaluator, &mut violations);
+            }
+        }
+    }
+
+    if format == CliFormat::Json {
+        print_json(&json!({ "violations": violations }));
+    } else if violations.is_empty() {
+        info!("No violations found.");
+    } else {
+        let mut last_profile = None;
+        for violation in &violations {
+            if last_profile != Some(&violation.profile) {
+                println!("{}:", violation.profile);
+                last_profile = Some(&violation.profile);
+            }
+            match &violation.location {
+                Some(location) => {
+                    println!("  {}: {} ({})", violation.rule, violation.message, location)
+                }
+                None => println!("  {}: {}", violation.rule, violation.message),
+            }
+        }
+    }
+
+    exit(if violations.is_empty() { 0 } else { 1 });
+}
+
+/// All `.profile`s in `USER_PROFILE_DIR` and `SYSTEM_PROFILE_DIR`, the ones firejail uses
+fn all_profiles() -> BTreeSet<String> {
+    let mut profiles = BTreeSet::new();
+    for location in [&*USER_PROFILE_DIR, &*SYSTEM_PROFILE_DIR] {
+        match read_names(location) {
+            Ok(names) => profiles.extend(names.into_iter().filter(|n| n.ends_with(".profile"))),
+            Err(err) => warn!("Failed to open {}: {}", location, err),
+        }
+    }
+    profiles
+}
+
+/// Return the lines matching `content` which are not ignored and the `ignore` line
+/// which ignored the other matching lines
+///
+/// A conditional line matches with its command if `evaluator` says its condition is true.
+fn find_matches<'a>(
+    content: &Content,
+    lines: &'a [EffectiveLine],
+    evaluator: &Evaluator,
+) -> (Vec<&'a EffectiveLine>, Option<&'a EffectiveLine>) {
+    let mut matches = Vec::new();
+    let mut ignored_by = None;
+
+    let is_match = |line: &EffectiveLine| {
+        *line.line.content == *content
+            || matches!(
+                (&*line.line.content, content),
+                (Content::Conditional(conditional), Content::Command(command))
+                    if conditional.command() == command
+                        && evaluator.evaluate(conditional.condition())
+            )
+    };
+    for line in lines.iter().filter(|line| is_match(line)) {
+        match line.ignored_by {
+            Some(ignore) => ignored_by = Some(&lines[ignore]),
+            None => matches.push(line),
+        }
+    }
+
+    (matches, ignored_by)
+}
+
+fn check_require(
+    profile: &str,
+    rule: &Rule,
+    content: &Content,
+    lines: &[EffectiveLine],
+    evaluator: &Evaluator,
+    violations: &mut Vec<Violation>,
+) {
+    let (matches, ignored_by) = find_matches(content, lines, evaluator);
+    if !matches.is_empty() {
+        return;
+    }
+
+    let line = content.to_string().trim_end().to_string();
+    violations.push(match ignored_by {
+        Some(ignore) => Violation {
+            profile: profile.to_string(),
+            rule: rule.id.clone(),
+            message: rule
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("`{}` is ignored", line)),
+            location: Some(ignore.location()),
+            line: Some(ignore.line.content.to_string().trim_end().to_string()),
+        },
+        None => Violation {
+            profile: profile.to_string(),
+            rule: rule.id.clone(),
+            message: rule
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("missing `{}`", line)),
+            location: None,
+            line: None,
+        },
+    });
+}
+
+fn check_forbid(
+    profile: &str,
+    rule: &Rule,
+    content: &Content,
+    lines: &[EffectiveLine],
+    evaluator: &Evaluator,
+    violations: &mut Vec<Violation>,
+) {
+    for line in find_matches(content, lines, evaluator).0 {
+        let text = line.line.content.to_string().trim_end().to_string();
+        violations.push(Violation {
+            profile: profile.to_string(),
+            rule: rule.id.clone(),
+            message: rule
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("forbidden `{}`", text)),
+            location: Some(line.location()),
+            line: Some(text),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effective::effective_profile_from;
+    use crate::profile_stream::Condition;
+
+    const POLICY: &str = r#"
+        [[rule]]
+        id = "disable-common"
+        require = "include disable-common.inc"
+
+        [[rule]]
+        id = "no-ssh"
+        forbid = "noblacklist ${HOME}/.ssh"
+        message = "~/.ssh must stay blacklisted"
+    "#;
+
+    /// Check the profile `data` with the includes `files` against `POLICY`
+    fn violations(data: &str, files: &[(&str, &str)]) -> Vec<(String, Option<String>)> {
+        violations_with(data, files, &Evaluator::default())
+    }
+
+    fn violations_with(
+        data: &str,
+        files: &[(&str, &str)],
+        evaluator: &Evaluator,
+    ) -> Vec<(String, Option<String>)> {
+        let policy: Policy = toml::from_str(POLICY).unwrap();
+        let lines = effective_profile_from(data, files, evaluator);
+        let mut violations = Vec::new();
+        for rule in &policy.rules {
+            match (&rule.require, &rule.forbid) {
+                (Some(line), None) => check_require(
+                    "test.profile",
+                    rule,
+                    &line.parse().unwrap(),
+                    &lines,
+                    evaluator,
+                    &mut violations,
+                ),
+                (None, Some(line)) => check_forbid(
+                    "test.profile",
+                    rule,
+                    &line.parse().unwrap(),
+                    &lines,
+                    evaluator,
+                    &mut violations,
+                ),
+                _ => unreachable!(),
+            }
+        }
+        violations
+            .into_iter()
+            .map(|violation| (violation.rule, violation.location))
+            .collect()
+    }
+
+    #[test]
+    fn no_violations() {
+        assert!(violations("include disable-common.inc\nnoroot\n", &[]).is_empty());
+    }
+
+    #[test]
+    fn missing_line() {
+        assert_eq!(
+            violations("noroot\n", &[]),
+            [("disable-common".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn ignored_line() {
+        assert_eq!(
+            violations(
+                "include foo.local\ninclude disable-common.inc\n",
+                &[("foo.local", "ignore include disable-common.inc\n")]
+            ),
+            [(
+                "disable-common".to_string(),
+                Some("foo.local:1".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn forbidden_line_in_include() {
+        assert_eq!(
+            violations(
+                "include foo.local\ninclude disable-common.inc\n",
+                &[("foo.local", "# ssh\nnoblacklist ${HOME}/.ssh\n")]
+            ),
+            [("no-ssh".to_string(), Some("foo.local:2".to_string()))]
+        );
+    }
+
+    #[test]
+    fn ignored_forbidden_line() {
+        assert!(violations(
+            "ignore noblacklist ${HOME}/.ssh\ninclude disable-common.inc\nnoblacklist ${HOME}/.ssh\n",
+            &[]
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn conditional_lines_are_not_active() {
+        assert_eq!(
+            violations(
+                "?HAS_X11: include disable-common.inc\n?HAS_NET: noblacklist ${HOME}/.ssh\n",
+                &[]
+            ),
+            [("disable-common".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn conditional_lines_with_true_condition() {
+        let evaluator = Evaluator::new(&Default::default(), [Condition::HasX11, Condition::HasNet]);
+        assert_eq!(
+            violations_with(
+                "?HAS_X11: include disable-common.inc\n?HAS_NET: noblacklist ${HOME}/.ssh\n",
+                &[],
+                &evaluator
+            ),
+            [("no-ssh".to_string(), Some("test.profile:2".to_string()))]
+        );
+    }
+
+    #[test]
+    fn conditional_ignore() {
+        let profile =
+            "?HAS_NODBUS: ignore include disable-common.inc\ninclude disable-common.inc\n";
+        assert!(violations(profile, &[]).is_empty());
+        assert_eq!(
+            violations_with(
+                profile,
+                &[],
+                &Evaluator::new(&Default::default(), [Condition::HasNodbus])
+            ),
+            [(
+                "disable-common".to_string(),
+                Some("test.profile:1".to_string())
+            )]
+        );
+    }
+}
@@ -21,55 +21,75 @@
 
 #![allow(dead_code)] // Some methods are for future use, others are USED! (=false positive)
 
+use crate::config::CONFIG;
 use crate::location::Location;
-use crate::{SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
+use crate::{EXTRA_PROFILE_DIRS, SYSTEM_PROFILE_DIR, USER_PROFILE_DIR};
 use bitflags::bitflags;
 use lazy_static::lazy_static;
 use log::{debug, warn};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::error::Error as StdError;
 use std::fs::{read_dir, read_to_string};
 use std::io;
 use std::path::{Path, PathBuf};
 
+/// The built-in shortnames, they can be extended or overridden in the config file
+pub const DEFAULT_SHORTNAMES: [(&str, &str); 27] = [
+    ("abs", "allow-bin-sh.inc"),
+    ("acd", "allow-common-devel.inc"),
+    ("ag", "allow-gjs.inc"),
+    ("aj", "allow-java.inc"),
+    ("al", "allow-lua.inc"),
+    ("an", "allow-nodejs.inc"),
+    ("ap", "allow-perl.inc"),
+    ("app", "allow-php.inc"),
+    ("ap2", "allow-python2.inc"),
+    ("ap3", "allow-python3.inc"),
+    ("ar", "allow-ruby.inc"),
+    ("as", "allow-ssh.inc"),
+    ("dc", "disable-common.inc"),
+    ("dd", "disable-devel.inc"),
+    ("de", "disable-exec.inc"),
+    ("di", "disable-interpreters.inc"),
+    ("dp", "disable-programs.inc"),
+    ("dpm", "disable-passwdmgr.inc"),
+    ("ds", "disable-shell.inc"),
+    ("dwm", "disable-write-mnt.inc"),
+    ("dX", "disable-X11.inc"),
+    ("dx", "disable-xdg.inc"),
+    ("wc", "whitelist-common.inc"),
+    ("wrc", "whitelist-run-common.inc"),
+    ("wruc", "whitelist-runuser-common.inc"),
+    ("wusc", "whitelist-usr-share-common.inc"),
+    ("wvc", "whitelist-var-common.inc"),
+];
+
 lazy_static! {
-    /// `lazy_static`: HashMap with the shortnames used by [`complete_name`]
-    static ref SHORTNAMES: HashMap<&'static str, &'static str> = [
-        ("abs", "allow-bin-sh.inc"),
-        ("acd", "allow-common-devel.inc"),
-        ("ag", "allow-gjs.inc"),
-        ("aj", "allow-java.inc"),
-        ("al", "allow-lua.inc"),
-        ("an", "allow-nodejs.inc"),
-        ("ap", "allow-perl.inc"),
-        ("app", "allow-php.inc"),
-        ("ap2", "allow-python2.inc"),
-        ("ap3", "allow-python3.inc"),
-        ("ar", "allow-ruby.inc"),
-        ("as", "allow-ssh.inc"),
-        ("dc", "disable-common.inc"),
-        ("dd", "disable-devel.inc"),
-        ("de", "disable-exec.inc"),
-        ("di", "disable-interpreters.inc"),
-        ("dp", "disable-programs.inc"),
-        ("dpm", "disable-passwdmgr.inc"),
-        ("ds", "disable-shell.inc"),
-        ("dwm", "disable-write-mnt.inc"),
-        ("dX", "disable-X11.inc"),
-        ("dx", "disable-xdg.inc"),
-        ("wc", "whitelist-common.inc"),
-        ("wrc", "whitelist-run-common.inc"),
-        ("wruc", "whitelist-runuser-common.inc"),
-        ("wusc", "whitelist-usr-share-common.inc"),
-        ("wvc", "whitelist-var-common.inc"),
-    ]
-    // TODO: Use .into_iter().collect() when we upgrade to rust 2021 edition
-    .iter()
-    .copied()
-    .collect();
+    /// `lazy_static`: Map with the shortnames used by [`complete_name`]
+    ///
+    /// This are the [`DEFAULT_SHORTNAMES`] merged with the `[shortnames]` from the config file.
+    pub static ref SHORTNAMES: BTreeMap<String, String> = merge_shortnames(&CONFIG.shortnames);
 }
 
+/// Merge the [`DEFAULT_SHORTNAMES`] with `configured`, `.profile` is added where necessary
+///
+/// The config file rejects values which contain a `/` or start with a `@`, so the values are
+/// plain profile names which are completed without looking up other shortnames.
+fn merge_shortnames(configured: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    let mut shortnames = DEFAULT_SHORTNAMES
+        .into_iter()
+        .map(|(short_name, long_name)| (short_name.to_string(), long_name.to_string()))
+        .collect::<BTreeMap<_, _>>();
+    for (short_name, long_name) in configured {
+        shortnames.insert(short_name.clone(), add_extension(long_name).into_owned());
+    }
+    shortnames
+}
+
+/// The `.local`s which are included by every profile
+pub const GLOBALS: [&str; 3] = ["pre-globals.local", "globals.local", "post-globals.local"];
+
 bitflags! {
     /// Flags for creating a new instance of profile
     pub struct ProfileFlags: u8 {
@@ -85,6 +105,8 @@ bitflags! {
         const DENY_BY_PATH      = 0b_0001_0000;
         /// Assume that the profile exists in the location with the highest priority
         const ASSUME_EXISTENCE  = 0b_0010_0000;
+        /// Search under the additional profile dirs (`--profile-dir`, ...) (default)
+        const LOOKUP_EXTRA      = 0b_0100_0000;
     }
 }
 impl ProfileFlags {
@@ -110,10 +132,10 @@ impl ProfileFlags {
         self & !other
     }
 }
-/// Default is `LOOKUP_CWD`, `LOOKUP_USER` and `LOOKUP_SYSTEM`
+/// Default is `LOOKUP_CWD`, `LOOKUP_USER`, `LOOKUP_EXTRA` and `LOOKUP_SYSTEM`
 impl Default for ProfileFlags {
     fn default() -> Self {
-        Self::LOOKUP_CWD | Self::LOOKUP_USER | Self::LOOKUP_SYSTEM
+        Self::LOOKUP_CWD | Self::LOOKUP_USER | Self::LOOKUP_EXTRA | Self::LOOKUP_SYSTEM
     }
 }
 
@@ -184,7 +206,7 @@ impl<'a> Profile<'a> {
     /// [`ErrorContext`]: struct.ErrorContext.html
     pub fn new(name: &'a str, flags: ProfileFlags) -> Result<Self, Error> {
         let raw_name = Cow::Borrowed(name);
-        let full_name = complete_name(name, flags);
+        let full_name = complete_name(name, flags)?;
 
         debug!("Expanded profile-name '{}' to '{}'.", raw_name, full_name);
 
@@ -326,22 +348,45 @@ impl<'a> Profile<'a> {
 /// Complete a profile name
 ///
 /// - extract the basename if `name` is a path
-/// - expand shortnames if possible
+/// - expand shortnames if `name` starts with `@` (e.g. `@dc` is `disable-common.inc`)
 /// - add `.profile` if necessary
 ///
+/// # Errors
+///
+/// - [`Error::UnknownShortname`]
+///
 /// # Panics
 ///
 /// This functions panics if `name` contains a `/` and flags does not contain `DENY_BY_PATH`.
-pub fn complete_name(name: &str, flags: ProfileFlags) -> Cow<'_, str> {
+pub fn complete_name(name: &str, flags: ProfileFlags) -> Result<Cow<'_, str>, Error> {
+    complete_name_with(name, flags, &SHORTNAMES)
+}
+
+/// [`complete_name`] with the shortnames from `shortnames`
+fn complete_name_with<'a>(
+    name: &'a str,
+    flags: ProfileFlags,
+    shortnames: &'a BTreeMap<String, String>,
+) -> Result<Cow<'a, str>, Error> {
     if name.contains('/') {
         if flags.contains(ProfileFlags::DENY_BY_PATH) {
             panic!("Profile-names must not contain '/'.");
         } else {
-            Cow::Borrowed(name.rsplit('/').next().unwrap())
+            Ok(Cow::Borrowed(name.rsplit('/').next().unwrap()))
         }
-    } else if let Some(long_name) = SHORTNAMES.get(name) {
-        Cow::Borrowed(long_name)
-    } else if name.ends_with(".inc") || name.ends_with(".local") || name.ends_with(".profile") {
+    } else if let Some(short_name) = name.strip_prefix('@') {
+        shortnames
+            .get(short_name)
+            .map(|long_name| Cow::Borrowed(long_name.as_str()))
+            .ok_or_else(|| Error::UnknownShortname(name.to_string()))
+    } else {
+        Ok(add_extension(name))
+    }
+}
+
+/// Add `.profile` to `name` unless it ends with `.inc`, `.local` or `.profile`
+fn add_extension(name: &str) -> Cow<'_, str> {
+    if name.ends_with(".inc") || name.ends_with(".local") || name.ends_with(".profile") {
         Cow::Borrowed(name)
     } else {
         Cow::Owned(name.to_string() + ".profile")
@@ -357,44 +402,56 @@ pub fn complete_name(name: &str, flags: ProfileFlags) -> Cow<'_, str> {
 /// Search order:
 ///   1. `LOOKUP_CWD` (`.`)
 ///   2. `LOOKUP_USER` (USER_PROFILE_DIR (`~/.config/firejail`))
-///   3. `LOOKUP_SYSTEM` (SYSTEM_PROFILE_DIR (`/etc/firejail`))
+///   3. `LOOKUP_EXTRA` (EXTRA_PROFILE_DIRS (`--profile-dir`, `$FJP_PROFILE_PATH`, config))
+///   4. `LOOKUP_SYSTEM` (SYSTEM_PROFILE_DIR (`/etc/firejail`))
 fn lookup_profile(name: &str, flags: ProfileFlags) -> Option<PathBuf> {
-    macro_rules! black_magic {
-        (if $cond:expr => $location:expr) => {
-            if $cond {
-                if flags.contains(ProfileFlags::ASSUME_EXISTENCE) {
-                    Some($location.get_profile_path(name))
-                } else {
-                    match read_dir($location.as_ref()) {
-                        Ok(files) => files
-                            .filter_map(|ent| match ent {
-                                Ok(ent) => Some(ent),
-                                Err(err) => {
-                                    warn!("There was a error in the lookup of: {}", err);
-                                    None
-                                }
-                            })
-                            .find(|ent| ent.file_name() == name)
-                            .map(|ent| ent.path()),
+    lookup_profile_in(name, flags, search_path(flags))
+}
+
+/// [`lookup_profile`] in `locations` instead of the locations specified in `flags`
+fn lookup_profile_in<'a>(
+    name: &str,
+    flags: ProfileFlags,
+    locations: impl IntoIterator<Item = &'a Location>,
+) -> Option<PathBuf> {
+    locations.into_iter().find_map(|location| {
+        if flags.contains(ProfileFlags::ASSUME_EXISTENCE) {
+            Some(location.get_profile_path(name))
+        } else {
+            match read_dir(location) {
+                Ok(files) => files
+                    .filter_map(|ent| match ent {
+                        Ok(ent) => Some(ent),
                         Err(err) => {
-                            warn!("Failed to open {}: {}", $location, err);
+                            warn!("There was a error in the lookup of: {}", err);
                             None
                         }
-                    }
+                    })
+                    .find(|ent| ent.file_name() == name)
+                    .map(|ent| ent.path()),
+                Err(err) => {
+                    warn!("Failed to open {}: {}", location, err);
+                    None
                 }
-            } else {
-                None
             }
-        };
+        }
+    })
+}
+
+/// Return the locations specified in `flags` in the order in which they are searched
+pub fn search_path(flags: ProfileFlags) -> impl Iterator<Item = &'static Location> {
+    lazy_static! {
+        static ref CWD: Location = Location::from(".");
     }
 
-    black_magic!(if flags.contains(ProfileFlags::LOOKUP_CWD) => Location::from("."))
-        .or_else(
-            || black_magic!(if flags.contains(ProfileFlags::LOOKUP_USER) => &*USER_PROFILE_DIR),
-        )
-        .or_else(
-            || black_magic!(if flags.contains(ProfileFlags::LOOKUP_SYSTEM) => &*SYSTEM_PROFILE_DIR),
-        )
+    let cwd = Some(&*CWD).filter(|_| flags.contains(ProfileFlags::LOOKUP_CWD));
+    let user = Some(&*USER_PROFILE_DIR).filter(|_| flags.contains(ProfileFlags::LOOKUP_USER));
+    let extra = EXTRA_PROFILE_DIRS
+        .iter()
+        .filter(move |_| flags.contains(ProfileFlags::LOOKUP_EXTRA));
+    let system = Some(&*SYSTEM_PROFILE_DIR).filter(|_| flags.contains(ProfileFlags::LOOKUP_SYSTEM));
+
+    cwd.into_iter().chain(user).chain(extra).chain(system)
 }
 
 /// Profile Error
@@ -422,6 +479,9 @@ pub enum Error {
     /// (i.e. [`path`](Profile::path)  is `None`).
     #[error("Called read on a Profile without a path.")]
     NoPath,
+    /// Occurs when a name starts with `@` but there is no such shortname.
+    #[error("Unknown shortname '{0}', see `fjp shortnames`.")]
+    UnknownShortname(String),
     /// Wraps an [I/O Error](std::io::Error).
     #[error("{0}")]
     Io(#[from] io::Error),
@@ -430,6 +490,7 @@ pub enum Error {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::TempDir;
 
     #[test]
     fn profile_flags_with() {
@@ -447,26 +508,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn lookup_profile_search_order() {
+        let dir = TempDir::new();
+        dir.write("user/foo.profile", "");
+        dir.write("extra/foo.profile", "");
+        dir.write("extra/bar.profile", "");
+        dir.write("system/bar.profile", "");
+        dir.write("system/baz.profile", "");
+        let locations = ["user", "extra", "missing", "system"]
+            .map(|name| Location::from(dir.path().join(name)));
+
+        let lookup = |name, flags| lookup_profile_in(name, flags, &locations);
+        let empty = ProfileFlags::empty();
+        assert_eq!(
+            lookup("foo.profile", empty),
+            Some(dir.path().join("user/foo.profile"))
+        );
+        assert_eq!(
+            lookup("bar.profile", empty),
+            Some(dir.path().join("extra/bar.profile"))
+        );
+        assert_eq!(
+            lookup("baz.profile", empty),
+            Some(dir.path().join("system/baz.profile"))
+        );
+        assert_eq!(lookup("qux.profile", empty), None);
+        assert_eq!(
+            lookup("qux.profile", ProfileFlags::ASSUME_EXISTENCE),
+            Some(dir.path().join("user/qux.profile"))
+        );
+    }
+
     #[test]
     fn complete_name_path() {
         assert_eq!(
-            complete_name("/etc/firejail/gnome-clocks.profile", ProfileFlags::empty()),
+            complete("/etc/firejail/gnome-clocks.profile", ProfileFlags::empty()).unwrap(),
             "gnome-clocks.profile"
         );
         assert_eq!(
-            complete_name("/etc/firejail/gnome-clocks", ProfileFlags::empty()),
+            complete("/etc/firejail/gnome-clocks", ProfileFlags::empty()).unwrap(),
             "gnome-clocks"
         );
         assert_eq!(
-            complete_name("etc/firejail/gnome-clocks", ProfileFlags::empty()),
+            complete("etc/firejail/gnome-clocks", ProfileFlags::empty()).unwrap(),
             "gnome-clocks"
         );
         assert_eq!(
-            complete_name("~/etc/firejail/gnome-clocks.profile", ProfileFlags::empty()),
+            complete("~/etc/firejail/gnome-clocks.profile", ProfileFlags::empty()).unwrap(),
             "gnome-clocks.profile"
         );
         assert_eq!(
-            complete_name("./gnome-clocks.local", ProfileFlags::empty()),
+            complete("./gnome-clocks.local", ProfileFlags::empty()).unwrap(),
             "gnome-clocks.local"
         );
     }
@@ -474,7 +567,7 @@ mod tests {
     #[test]
     #[should_panic(expected = "Profile-names must not contain '/'.")]
     fn complete_name_path_deny_by_path_1() {
-        complete_name(
+        let _ = complete(
             "/etc/firejail/gnome-clocks.profile",
             ProfileFlags::DENY_BY_PATH,
         );
@@ -483,19 +576,19 @@ mod tests {
     #[test]
     #[should_panic(expected = "Profile-names must not contain '/'.")]
     fn complete_name_path_deny_by_path_2() {
-        complete_name("/etc/firejail/gnome-clocks", ProfileFlags::DENY_BY_PATH);
+        let _ = complete("/etc/firejail/gnome-clocks", ProfileFlags::DENY_BY_PATH);
     }
 
     #[test]
     #[should_panic(expected = "Profile-names must not contain '/'.")]
     fn complete_name_path_deny_by_path_3() {
-        complete_name("etc/firejail/gnome-clocks", ProfileFlags::DENY_BY_PATH);
+        let _ = complete("etc/firejail/gnome-clocks", ProfileFlags::DENY_BY_PATH);
     }
 
     #[test]
     #[should_panic(expected = "Profile-names must not contain '/'.")]
     fn complete_name_path_deny_by_path_4() {
-        complete_name(
+        let _ = complete(
             "~/etc/firejail/gnome-clocks.profile",
             ProfileFlags::DENY_BY_PATH,
         );
@@ -504,28 +597,73 @@ mod tests {
     #[test]
     #[should_panic(expected = "Profile-names must not contain '/'.")]
     fn complete_name_path_deny_by_path_5() {
-        complete_name("./gnome-clocks.local", ProfileFlags::DENY_BY_PATH);
+        let _ = complete("./gnome-clocks.local", ProfileFlags::DENY_BY_PATH);
+    }
+
+    /// [`complete_name`] with [`shortnames`] instead of the shortnames from the config file
+    fn complete(name: &str, flags: ProfileFlags) -> Result<String, Error> {
+        complete_name_with(name, flags, &shortnames()).map(Cow::into_owned)
+    }
+
+    fn shortnames() -> BTreeMap<String, String> {
+        merge_shortnames(
+            &[("ff", "firefox"), ("dc", "my-disable-common.inc")]
+                .into_iter()
+                .map(|(short_name, long_name)| (short_name.to_string(), long_name.to_string()))
+                .collect(),
+        )
     }
 
     #[test]
     fn complete_name_short_names() {
-        for (sname, lname) in &*SHORTNAMES {
-            assert_eq!(complete_name(sname, ProfileFlags::empty()), *lname,);
+        let shortnames = shortnames();
+        for (sname, lname) in DEFAULT_SHORTNAMES.into_iter().filter(|(s, _)| *s != "dc") {
+            assert_eq!(
+                complete_name_with(&format!("@{}", sname), ProfileFlags::empty(), &shortnames)
+                    .unwrap(),
+                lname,
+            );
         }
     }
 
+    #[test]
+    fn complete_name_configured_short_names() {
+        let shortnames = shortnames();
+        assert_eq!(
+            complete_name_with("@ff", ProfileFlags::empty(), &shortnames).unwrap(),
+            "firefox.profile"
+        );
+        assert_eq!(
+            complete_name_with("@dc", ProfileFlags::empty(), &shortnames).unwrap(),
+            "my-disable-common.inc"
+        );
+    }
+
+    #[test]
+    fn complete_name_unknown_short_name() {
+        assert!(matches!(
+            complete_name_with("@nope", ProfileFlags::empty(), &shortnames()),
+            Err(Error::UnknownShortname(name)) if name == "@nope"
+        ));
+    }
+
+    #[test]
+    fn complete_name_short_names_without_prefix() {
+        assert_eq!(complete("as", ProfileFlags::empty()).unwrap(), "as.profile");
+    }
+
     #[test]
     fn complete_name_inc_local_profile() {
         assert_eq!(
-            complete_name("libreoffice.inc", ProfileFlags::empty()),
+            complete("libreoffice.inc", ProfileFlags::empty()).unwrap(),
             "libreoffice.inc"
         );
         assert_eq!(
-            complete_name("libreoffice.local", ProfileFlags::empty()),
+            complete("libreoffice.local", ProfileFlags::empty()).unwrap(),
             "libreoffice.local"
         );
         assert_eq!(
-            complete_name("libreoffice.profile", ProfileFlags::empty()),
+            complete("libreoffice.profile", ProfileFlags::empty()).unwrap(),
             "libreoffice.profile"
         );
     }
@@ -533,7 +671,7 @@ mod tests {
     #[test]
     fn complete_name_append_profile() {
         assert_eq!(
-            complete_name("bijiben", ProfileFlags::empty()),
+            complete("bijiben", ProfileFlags::empty()).unwrap(),
             "bijiben.profile"
         );
     }
@@ -22,6 +22,7 @@
 #![allow(clippy::cognitive_complexity)]
 
 use crate::utils::join;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::{Borrow, BorrowMut};
 use std::fmt;
 use std::iter::FromIterator;
@@ -31,8 +32,11 @@ use std::sync::Arc;
 use std::vec;
 
 /// An abstract stream of lines in a firejail profile
-#[derive(Clone, Debug)]
+///
+/// Serialized as `{"lines": [...]}`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProfileStream {
+    #[serde(rename = "lines")]
     inner: Vec<Line>,
 }
 impl ProfileStream {
@@ -211,9 +215,14 @@ impl FromStr for ProfileStream {
 //
 
 /// A profile-line
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Deserializing fails if `content` does not parse back to itself, e.g. because a string
+/// in it contains a line break and would add more lines to the profile.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedLine")]
 pub struct Line {
     /// The line number of this line if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub lineno: Option<usize>,
     /// The content of this line
     pub content: Arc<Content>,
@@ -236,13 +245,51 @@ impl AsRef<Content> for Line {
         &self.content
     }
 }
+impl TryFrom<UncheckedLine> for Line {
+    type Error = String;
+
+    fn try_from(line: UncheckedLine) -> Result<Self, Self::Error> {
+        let rendered = line.content.to_string();
+        let rendered = rendered.strip_suffix('\n').unwrap_or(&rendered);
+        if rendered.contains(&['\n', '\r'][..]) {
+            return Err(format!(
+                "`{}` contains a line break",
+                rendered.escape_debug()
+            ));
+        }
+        let reparsed = rendered
+            .parse::<Content>()
+            .unwrap_or_else(|invalid| invalid);
+        if reparsed != *line.content {
+            return Err(format!(
+                "`{}` does not parse back to the same line",
+                rendered.escape_debug()
+            ));
+        }
+        Ok(Self {
+            lineno: line.lineno,
+            content: line.content,
+        })
+    }
+}
+
+/// A [`Line`] as it is deserialized before it is checked
+#[derive(Deserialize)]
+struct UncheckedLine {
+    #[serde(default)]
+    lineno: Option<usize>,
+    content: Arc<Content>,
+}
 
 //
 // Content
 //
 
 /// The content of a profile-`Line`
-#[derive(Clone, Debug, PartialEq, Eq)]
+///
+/// Serialized as `{"type": "command", "value": {"command": "nonewprivs"}}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
 pub enum Content {
     Blank,
     Command(Command),
@@ -288,45 +335,59 @@ impl FromStr for Content {
 //
 
 /// A firejail command
+///
+/// Serialized as `{"command": "whitelist", "args": "${HOME}/foo"}`, the name of the command
+/// is the firejail command with spaces replaced by `-` (e.g. `net-none`).
 #[non_exhaustive]
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", content = "args", rename_all = "kebab-case")]
 pub enum Command {
     AllowDebuggers,
     Allusers,
     Apparmor,
-    Bind(String, String),
-    Blacklist(String),
-    BlacklistNolog(String),
+    Bind(ProfilePath, ProfilePath),
+    Blacklist(ProfilePath),
+    BlacklistNolog(ProfilePath),
     Caps,
+    #[serde(rename = "caps.drop-all")]
     CapsDropAll,
+    #[serde(rename = "caps.drop")]
     CapsDrop(Vec<Capabilities>),
+    #[serde(rename = "caps.keep")]
     CapsKeep(Vec<Capabilities>),
+    #[serde(rename = "dbus-user")]
     DBusUser(DBusPolicy),
+    #[serde(rename = "dbus-user.own")]
     DBusUserOwn(String),
+    #[serde(rename = "dbus-user.talk")]
     DBusUserTalk(String),
+    #[serde(rename = "dbus-system")]
     DBusSystem(DBusPolicy),
+    #[serde(rename = "dbus-system.own")]
     DBusSystemOwn(String),
+    #[serde(rename = "dbus-system.talk")]
     DBusSystemTalk(String),
     DisableMnt,
     /// `Env(String::from("WEBKIT_FORCE_SANDBOX"), String::from("0"))`: `env WEBKIT_FORCE_SANDBOX=0`
     Env(String, String),
     Hostname(String),
-    Ignore(String),
+    /// `Ignore(IgnorePattern::Prefix(String::from("noexec")))`: `ignore noexec`
+    Ignore(IgnorePattern),
     /// TODO: Recusive `ProfileStream`s
     Include(String),
     IpcNamespace,
     JoinOrStart(String),
     MachineId,
     MemoryDenyWriteExecute,
-    Mkdir(String),
-    Mkfile(String),
+    Mkdir(ProfilePath),
+    Mkfile(ProfilePath),
     Name(String),
     Netfilter,
     NetNone,
     No3d,
-    Noblacklist(String),
+    Noblacklist(ProfilePath),
     Nodvd,
-    Noexec(String),
+    Noexec(ProfilePath),
     Nogroups,
     Noinput,
     Nonewprivs,
@@ -335,40 +396,64 @@ pub enum Command {
     Notv,
     Nou2f,
     Novideo,
-    Nowhitelist(String),
+    Nowhitelist(ProfilePath),
+    Overlay,
+    OverlayNamed(String),
+    OverlayTmpfs,
     /// `Private(None)`: `private`<br>
-    /// `Private(Some(String::from("${HOME}/spam")))`: `private ${HOME}/spam`
-    Private(Option<String>),
+    /// `Private(Some("${HOME}/spam".parse().unwrap()))`: `private ${HOME}/spam`
+    Private(Option<ProfilePath>),
     PrivateBin(Vec<String>),
     PrivateCache,
     PrivateCwd(String),
     PrivateDev,
     PrivateEtc(Vec<String>),
+    PrivateHome(Vec<String>),
     PrivateLib(Option<Vec<String>>),
     PrivateOpt(Vec<String>),
     PrivateSrv(Vec<String>),
     PrivateTmp,
     Protocol(Vec<Protocol>),
     Quiet,
-    ReadOnly(String),
-    ReadWrite(String),
+    ReadOnly(ProfilePath),
+    ReadWrite(ProfilePath),
     Rmenv(String),
     /// `Seccomp(None)`: `seccomp`<br>
     /// `Seccomp(Some(vec!["!chroot".to_string()]))`: `seccomp !chroot`
     Seccomp(Option<Vec<String>>),
+    #[serde(rename = "seccomp.block-secondary")]
     SeccompBlockSecondary,
+    #[serde(rename = "seccomp.drop")]
     SeccompDrop(Vec<String>),
     SeccompErrorAction(SeccompErrorAction),
     ShellNone,
-    Tmpfs(String),
+    Tmpfs(ProfilePath),
     Tracelog,
-    Whitelist(String),
+    Whitelist(ProfilePath),
+    #[serde(rename = "writable-etc")]
     WriteableEtc,
     WritableRunUser,
     WritableVar,
     WritableVarLog,
+    /// `X11(None)`: `x11`<br>
+    /// `X11(Some(X11Server::Xpra))`: `x11 xpra`
+    X11(Option<X11Server>),
     X11None,
 }
+impl Command {
+    /// Returns the paths used by this command, e.g. `/foo` for `blacklist /foo`
+    pub fn paths(&self) -> Vec<&ProfilePath> {
+        use Command::*;
+
+        match self {
+            Bind(src_path, dst_path) => vec![src_path, dst_path],
+            Blacklist(path) | BlacklistNolog(path) | Mkdir(path) | Mkfile(path)
+            | Noblacklist(path) | Noexec(path) | Nowhitelist(path) | Private(Some(path))
+            | ReadOnly(path) | ReadWrite(path) | Tmpfs(path) | Whitelist(path) => vec![path],
+            _ => Vec::new(),
+        }
+    }
+}
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Command::*;
@@ -416,6 +501,9 @@ impl fmt::Display for Command {
             Nou2f => write!(f, "nou2f"),
             Novideo => write!(f, "novideo"),
             Nowhitelist(path) => write!(f, "nowhitelist {}", path),
+            Overlay => write!(f, "overlay"),
+            OverlayNamed(name) => write!(f, "overlay-named {}", name),
+            OverlayTmpfs => write!(f, "overlay-tmpfs"),
             Private(None) => write!(f, "private"),
             Private(Some(path)) => write!(f, "private {}", path),
             PrivateBin(bins) => write!(f, "private-bin {}", bins.join(",")),
@@ -423,6 +511,7 @@ impl fmt::Display for Command {
             PrivateCwd(path) => write!(f, "private-cwd {}", path),
             PrivateDev => write!(f, "private-dev"),
             PrivateEtc(files) => write!(f, "private-etc {}", files.join(",")),
+            PrivateHome(files) => write!(f, "private-home {}", files.join(",")),
             PrivateLib(None) => write!(f, "private-lib"),
             PrivateLib(Some(files)) => write!(f, "private-lib {}", files.join(",")),
             PrivateOpt(files) => write!(f, "private-opt {}", files.join(",")),
@@ -446,6 +535,8 @@ impl fmt::Display for Command {
             WritableRunUser => write!(f, "writable-run-user"),
             WritableVar => write!(f, "writable-var"),
             WritableVarLog => write!(f, "writable-var-log"),
+            X11(None) => write!(f, "x11"),
+            X11(Some(server)) => write!(f, "x11 {}", server),
             X11None => write!(f, "x11 none"),
         }
     }
@@ -463,14 +554,12 @@ impl FromStr for Command {
         } else if line == "apparmor" {
             Apparmor
         } else if let Some(paths) = line.strip_prefix("bind ") {
-            paths
-                .split_once(',')
-                .map(|(src, dst)| Bind(src.to_string(), dst.to_string()))
-                .ok_or(Error::BadBind)?
+            let (src, dst) = paths.split_once(',').ok_or(Error::BadBind)?;
+            Bind(src.parse()?, dst.parse()?)
         } else if let Some(path) = line.strip_prefix("blacklist ") {
-            Blacklist(path.to_string())
+            Blacklist(path.parse()?)
         } else if let Some(path) = line.strip_prefix("blacklist-nolog ") {
-            BlacklistNolog(path.to_string())
+            BlacklistNolog(path.parse()?)
         } else if line == "caps" {
             Caps
         } else if line == "caps.drop all" {
@@ -505,7 +594,7 @@ impl FromStr for Command {
         } else if let Some(hostname) = line.strip_prefix("hostname ") {
             Hostname(hostname.to_string())
         } else if let Some(line) = line.strip_prefix("ignore ") {
-            Ignore(line.to_string())
+            Ignore(line.parse()?)
         } else if let Some(other_profile) = line.strip_prefix("include ") {
             Include(other_profile.to_string())
         } else if line == "ipc-namespace" {
@@ -517,9 +606,9 @@ impl FromStr for Command {
         } else if line == "memory-deny-write-execute" {
             MemoryDenyWriteExecute
         } else if let Some(path) = line.strip_prefix("mkdir ") {
-            Mkdir(path.to_string())
+            Mkdir(path.parse()?)
         } else if let Some(path) = line.strip_prefix("mkfile ") {
-            Mkfile(path.to_string())
+            Mkfile(path.parse()?)
         } else if let Some(sandboxname) = line.strip_prefix("name ") {
             Name(sandboxname.to_string())
         } else if line == "netfilter" {
@@ -529,11 +618,11 @@ impl FromStr for Command {
         } else if line == "no3d" {
             No3d
         } else if let Some(path) = line.strip_prefix("noblacklist ") {
-            Noblacklist(path.to_string())
+            Noblacklist(path.parse()?)
         } else if line == "nodvd" {
             Nodvd
         } else if let Some(path) = line.strip_prefix("noexec ") {
-            Noexec(path.to_string())
+            Noexec(path.parse()?)
         } else if line == "nogroups" {
             Nogroups
         } else if line == "noinput" {
@@ -551,11 +640,17 @@ impl FromStr for Command {
         } else if line == "novideo" {
             Novideo
         } else if let Some(path) = line.strip_prefix("nowhitelist ") {
-            Nowhitelist(path.to_string())
+            Nowhitelist(path.parse()?)
+        } else if line == "overlay" {
+            Overlay
+        } else if let Some(name) = line.strip_prefix("overlay-named ") {
+            OverlayNamed(name.to_string())
+        } else if line == "overlay-tmpfs" {
+            OverlayTmpfs
         } else if line == "private" {
             Private(None)
         } else if let Some(path) = line.strip_prefix("private ") {
-            Private(Some(path.to_string()))
+            Private(Some(path.parse()?))
         } else if let Some(bins) = line.strip_prefix("private-bin ") {
             PrivateBin(bins.split(',').map(String::from).collect())
         } else if line == "private-cache" {
@@ -566,6 +661,8 @@ impl FromStr for Command {
             PrivateDev
         } else if let Some(files) = line.strip_prefix("private-etc ") {
             PrivateEtc(files.split(',').map(String::from).collect())
+        } else if let Some(files) = line.strip_prefix("private-home ") {
+            PrivateHome(files.split(',').map(String::from).collect())
         } else if line == "private-lib" {
             PrivateLib(None)
         } else if let Some(libs) = line.strip_prefix("private-lib ") {
@@ -586,9 +683,9 @@ impl FromStr for Command {
         } else if line == "quiet" {
             Quiet
         } else if let Some(path) = line.strip_prefix("read-only ") {
-            ReadOnly(path.to_string())
+            ReadOnly(path.parse()?)
         } else if let Some(path) = line.strip_prefix("read-write ") {
-            ReadWrite(path.to_string())
+            ReadWrite(path.parse()?)
         } else if let Some(name) = line.strip_prefix("rmenv ") {
             Rmenv(name.to_string())
         } else if line == "seccomp" {
@@ -604,11 +701,11 @@ impl FromStr for Command {
         } else if line == "shell none" {
             ShellNone
         } else if let Some(path) = line.strip_prefix("tmpfs ") {
-            Tmpfs(path.to_string())
+            Tmpfs(path.parse()?)
         } else if line == "tracelog" {
             Tracelog
         } else if let Some(path) = line.strip_prefix("whitelist ") {
-            Whitelist(path.to_string())
+            Whitelist(path.parse()?)
         } else if line == "writable-etc" {
             WriteableEtc
         } else if line == "writable-run-user" {
@@ -617,8 +714,12 @@ impl FromStr for Command {
             WritableVar
         } else if line == "writable-var-log" {
             WritableVarLog
+        } else if line == "x11" {
+            X11(None)
         } else if line == "x11 none" {
             X11None
+        } else if let Some(server) = line.strip_prefix("x11 ") {
+            X11(Some(server.parse()?))
         } else {
             return Err(Error::BadCommand);
         })
@@ -629,60 +730,52 @@ impl FromStr for Command {
 // Conditional
 //
 
-/// A condition with an conditional command
-#[non_exhaustive]
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
-pub enum Conditional {
-    BrowserAllowDrm(Command),
-    BrowserDisableU2f(Command),
-    HasAppimage(Command),
-    HasNet(Command),
-    HasNodbus(Command),
-    HasNosound(Command),
-    HasPrivate(Command),
-    HasX11(Command),
+/// A condition with an conditional command, e.g. `?HAS_X11: whitelist /tmp/.X11-unix`
+///
+/// Serialized as `{"condition": "HAS_NET", "command": {...}}`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conditional {
+    condition: Condition,
+    command: Command,
+}
+impl Conditional {
+    pub fn new(condition: Condition, command: Command) -> Self {
+        Self { condition, command }
+    }
+
+    /// The condition which must be true
+    pub fn condition(&self) -> Condition {
+        self.condition
+    }
+
+    /// The command which is executed if the condition is true
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
 }
 impl FromStr for Conditional {
     type Err = Error;
 
+    /// Like firejail, spaces and tabs between the `:` and the command are optional.
     fn from_str(line: &str) -> Result<Self, Self::Err> {
-        let mut splited_line = line.splitn(2, ' ');
-        let con = splited_line.next().unwrap();
-        let cmd = splited_line.next().ok_or(Error::EmptyCondition)?;
-
-        if con == "?BROWSER_ALLOW_DRM:" {
-            Ok(Self::BrowserAllowDrm(cmd.parse()?))
-        } else if con == "?BROWSER_DISABLE_U2F:" {
-            Ok(Self::BrowserDisableU2f(cmd.parse()?))
-        } else if con == "?HAS_APPIMAGE:" {
-            Ok(Self::HasAppimage(cmd.parse()?))
-        } else if con == "?HAS_NET:" {
-            Ok(Self::HasNet(cmd.parse()?))
-        } else if con == "?HAS_NODBUS:" {
-            Ok(Self::HasNodbus(cmd.parse()?))
-        } else if con == "?HAS_NOSOUND:" {
-            Ok(Self::HasNosound(cmd.parse()?))
-        } else if con == "?HAS_PRIVATE: " {
-            Ok(Self::HasPrivate(cmd.parse()?))
-        } else if con == "?HAS_X11:" {
-            Ok(Self::HasX11(cmd.parse()?))
-        } else {
-            Err(Error::BadCondition)
+        let (condition, command) = line
+            .strip_prefix('?')
+            .and_then(|line| line.split_once(':'))
+            .ok_or(Error::BadCondition)?;
+        let command = command.trim_start_matches(|c| c == ' ' || c == '\t');
+        if command.is_empty() {
+            return Err(Error::EmptyCondition);
         }
+
+        Ok(Self {
+            condition: condition.parse()?,
+            command: command.parse()?,
+        })
     }
 }
 impl fmt::Display for Conditional {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::BrowserAllowDrm(cmd) => write!(f, "?BROWSER_ALLOW_DRM: {}", cmd),
-            Self::BrowserDisableU2f(cmd) => write!(f, "?BROWSER_DISABLE_U2F: {}", cmd),
-            Self::HasAppimage(cmd) => write!(f, "?HAS_APPIMAGE: {}", cmd),
-            Self::HasNet(cmd) => write!(f, "?HAS_NET: {}", cmd),
-            Self::HasNodbus(cmd) => write!(f, "?HAS_NODBUS: {}", cmd),
-            Self::HasNosound(cmd) => write!(f, "?HAS_NOSOUND: {}", cmd),
-            Self::HasPrivate(cmd) => write!(f, "?HAS_PRIVATE: {}", cmd),
-            Self::HasX11(cmd) => write!(f, "?HAS_X11: {}", cmd),
-        }
+        write!(f, "?{}: {}", self.condition, self.command)
     }
 }
 
@@ -698,10 +791,14 @@ macro_rules! values {
         pub enum $T {
             $( $variant ),*
         }
+        impl $T {
+            /// All values as they are written in a profile
+            pub const VALUES: &'static [&'static str] = &[ $( $value ),* ];
+        }
         impl fmt::Display for $T {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 match self {
-                    $( Self::$variant => write!(f, $value), )*
+                    $( Self::$variant => write!(f, "{}", $value), )*
                 }
             }
         }
@@ -715,6 +812,18 @@ macro_rules! values {
                 }
             }
         }
+        impl Serialize for $T {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+        impl<'de> Deserialize<'de> for $T {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(de::Error::custom)
+            }
+        }
     };
 }
 
@@ -785,6 +894,375 @@ values! {
     }
 }
 
+//
+// Condition
+//
+
+values! {
+    /// A condition firejail supports in conditional lines
+    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Condition {
+        AllowTray = "ALLOW_TRAY",
+        BrowserAllowDrm = "BROWSER_ALLOW_DRM",
+        BrowserDisableU2f = "BROWSER_DISABLE_U2F",
+        HasAppimage = "HAS_APPIMAGE",
+        HasNet = "HAS_NET",
+        HasNodbus = "HAS_NODBUS",
+        HasNosound = "HAS_NOSOUND",
+        HasPrivate = "HAS_PRIVATE",
+        HasX11 = "HAS_X11",
+        _ = Error::BadCondition,
+    }
+}
+
+//
+// IgnorePattern
+//
+
+/// All commands with a short description
+///
+/// Commands with a fixed argument (`net none`) are listed with it. The first words are
+/// used to validate the prefixes of `ignore`, the language server uses the descriptions
+/// for completion and hover.
+pub const COMMANDS: &[(&str, &str)] = &[
+    (
+        "allow-debuggers",
+        "Allow tools such as strace and gdb inside the sandbox.",
+    ),
+    (
+        "allusers",
+        "All user home directories are visible inside the sandbox.",
+    ),
+    (
+        "apparmor",
+        "Enable AppArmor confinement with the firejail-default profile.",
+    ),
+    (
+        "bind",
+        "Mount-bind a directory or file on top of another one (`bind src,dst`).",
+    ),
+    ("blacklist", "Blacklist a directory or file."),
+    (
+        "blacklist-nolog",
+        "Blacklist a directory or file without logging violations.",
+    ),
+    ("caps", "Enable the default Linux capabilities filter."),
+    ("caps.drop all", "Drop all capabilities."),
+    (
+        "caps.drop",
+        "Drop the given capabilities (`caps.drop cap1,cap2`).",
+    ),
+    ("caps.keep", "Drop all capabilities except the given ones."),
+    (
+        "dbus-user",
+        "Set the policy for the D-Bus session bus: `filter` or `none`.",
+    ),
+    (
+        "dbus-user.own",
+        "Allow the application to own a name on the session bus.",
+    ),
+    (
+        "dbus-user.talk",
+        "Allow the application to talk to a name on the session bus.",
+    ),
+    (
+        "dbus-system",
+        "Set the policy for the D-Bus system bus: `filter` or `none`.",
+    ),
+    (
+        "dbus-system.own",
+        "Allow the application to own a name on the system bus.",
+    ),
+    (
+        "dbus-system.talk",
+        "Allow the application to talk to a name on the system bus.",
+    ),
+    (
+        "disable-mnt",
+        "Blacklist /mnt, /media, /run/mount and /run/media.",
+    ),
+    ("env", "Set an environment variable (`env NAME=VALUE`)."),
+    ("hostname", "Set the hostname of the sandbox."),
+    (
+        "ignore",
+        "Ignore all following lines starting with the given command.",
+    ),
+    ("include", "Include another profile file."),
+    ("ipc-namespace", "Enable a new IPC namespace."),
+    (
+        "join-or-start",
+        "Join the sandbox with the given name or start a new one.",
+    ),
+    ("machine-id", "Use a random machine-id in the sandbox."),
+    (
+        "memory-deny-write-execute",
+        "Prevent memory which is both writable and executable.",
+    ),
+    (
+        "mkdir",
+        "Create a directory in the user's home or /run/user before the sandbox starts.",
+    ),
+    (
+        "mkfile",
+        "Create a file in the user's home or /run/user before the sandbox starts.",
+    ),
+    ("name", "Set the name of the sandbox."),
+    ("netfilter", "Enable the default client network filter."),
+    ("net none", "Enable a new, unconnected network namespace."),
+    ("no3d", "Disable 3D hardware acceleration."),
+    (
+        "noblacklist",
+        "Disable the blacklist for a directory or file.",
+    ),
+    ("nodvd", "Disable DVD and audio CD devices."),
+    (
+        "noexec",
+        "Remount a directory or file noexec, nodev and nosuid.",
+    ),
+    ("nogroups", "Disable supplementary user groups."),
+    ("noinput", "Disable input devices."),
+    (
+        "nonewprivs",
+        "Set the NO_NEW_PRIVS prctl, no process can gain new privileges.",
+    ),
+    (
+        "noroot",
+        "Use a new user namespace with only the current user.",
+    ),
+    ("nosound", "Disable sound."),
+    ("notv", "Disable DVB TV devices."),
+    ("nou2f", "Disable U2F devices."),
+    ("novideo", "Disable video devices."),
+    (
+        "nowhitelist",
+        "Disable the whitelist for a directory or file.",
+    ),
+    (
+        "overlay",
+        "Mount a persistent overlay filesystem on top of /.",
+    ),
+    (
+        "overlay-named",
+        "Mount a persistent overlay filesystem with the given name on top of /.",
+    ),
+    (
+        "overlay-tmpfs",
+        "Mount a temporary overlay filesystem on top of /.",
+    ),
+    (
+        "private",
+        "Mount a new, empty home directory or use the given directory as home.",
+    ),
+    (
+        "private-bin",
+        "Build a new /bin with only the given programs.",
+    ),
+    ("private-cache", "Mount an empty tmpfs on top of ~/.cache."),
+    (
+        "private-cwd",
+        "Set the working directory inside the sandbox.",
+    ),
+    ("private-dev", "Build a new /dev with only a few devices."),
+    ("private-etc", "Build a new /etc with only the given files."),
+    (
+        "private-home",
+        "Build a new home directory with only the given files.",
+    ),
+    (
+        "private-lib",
+        "Build a new /lib with only the libraries needed by the application.",
+    ),
+    ("private-opt", "Build a new /opt with only the given files."),
+    ("private-srv", "Build a new /srv with only the given files."),
+    ("private-tmp", "Mount an empty tmpfs on top of /tmp."),
+    (
+        "protocol",
+        "Allow only the given socket protocols (`unix,inet,inet6,netlink,...`).",
+    ),
+    ("quiet", "Disable the output of firejail."),
+    ("read-only", "Make a directory or file read-only."),
+    ("read-write", "Make a directory or file read-write."),
+    ("rmenv", "Remove an environment variable."),
+    (
+        "seccomp",
+        "Enable the default seccomp filter, optionally with additional syscalls.",
+    ),
+    (
+        "seccomp.block-secondary",
+        "Block the secondary architectures in the seccomp filter.",
+    ),
+    (
+        "seccomp.drop",
+        "Enable a seccomp filter which blocks the given syscalls.",
+    ),
+    (
+        "seccomp-error-action",
+        "The action for blocked syscalls: kill, log or an errno.",
+    ),
+    ("shell none", "Run the program directly without a shell."),
+    ("tmpfs", "Mount a tmpfs on top of a directory."),
+    ("tracelog", "Log blacklist violations to syslog."),
+    (
+        "whitelist",
+        "Whitelist a directory or file, all other files in its parent are hidden.",
+    ),
+    ("writable-etc", "Mount /etc writable."),
+    (
+        "writable-run-user",
+        "Allow access to /run/user/$UID/systemd and /run/user/$UID/gnupg.",
+    ),
+    ("writable-var", "Mount /var writable."),
+    (
+        "writable-var-log",
+        "Use the real /var/log instead of a tmpfs.",
+    ),
+    (
+        "x11",
+        "Start a separate X11 server (`x11 xephyr`, `x11 xorg`, `x11 xpra` or `x11 xvfb`).",
+    ),
+    ("x11 none", "Blacklist the X11 sockets."),
+];
+
+/// Check whether `name` is the first word of a command
+fn is_command_name(name: &str) -> bool {
+    COMMANDS
+        .iter()
+        .any(|(command, _)| command.split(' ').next() == Some(name))
+}
+
+/// The argument of `ignore`
+///
+/// Like firejail, `ignore` suppresses all following lines which start with the pattern
+/// followed by a space or the end of the line. `ignore noexec` ignores `noexec /tmp` but
+/// not `noexec-foo`.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum IgnorePattern {
+    /// A complete command, e.g. `ignore private-dev` or `ignore noexec /tmp`
+    Command(Box<Command>),
+    /// The beginning of a command, e.g. `ignore noexec` or `ignore dbus-user.talk org.gnome`
+    Prefix(String),
+}
+impl IgnorePattern {
+    /// Check whether `command` is ignored by this pattern
+    pub fn matches(&self, command: &Command) -> bool {
+        let pattern = self.to_string();
+        let command = command.to_string();
+        command
+            .strip_prefix(&pattern)
+            .map_or(false, |rest| rest.is_empty() || rest.starts_with(' '))
+    }
+}
+impl fmt::Display for IgnorePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command(command) => write!(f, "{}", command),
+            Self::Prefix(prefix) => write!(f, "{}", prefix),
+        }
+    }
+}
+impl FromStr for IgnorePattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(command) = s.parse::<Command>() {
+            return Ok(Self::Command(Box::new(command)));
+        }
+
+        let name = s.split(' ').next().unwrap_or_default();
+        if is_command_name(name) && !s.ends_with(' ') && !s.contains("  ") {
+            Ok(Self::Prefix(s.to_string()))
+        } else {
+            Err(Error::BadIgnore)
+        }
+    }
+}
+impl Serialize for IgnorePattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for IgnorePattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+//
+// Macro
+//
+
+values! {
+    /// A macro in a path, e.g. `${HOME}`
+    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Macro {
+        Cfg = "${CFG}",
+        Desktop = "${DESKTOP}",
+        Documents = "${DOCUMENTS}",
+        Downloads = "${DOWNLOADS}",
+        Home = "${HOME}",
+        Music = "${MUSIC}",
+        Path = "${PATH}",
+        Pictures = "${PICTURES}",
+        Runuser = "${RUNUSER}",
+        Videos = "${VIDEOS}",
+        _ = Error::BadMacro,
+    }
+}
+
+//
+// ProfilePath
+//
+
+/// A path used by a command, e.g. `${HOME}/.config/foo` or `/usr/share/foo*`
+///
+/// All macros in a `ProfilePath` are known [`Macro`]s.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ProfilePath(String);
+impl ProfilePath {
+    /// Returns the path as it is written in the profile
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns all macros used in this path
+    pub fn macros(&self) -> impl Iterator<Item = Macro> + '_ {
+        self.0.match_indices("${").filter_map(|(start, _)| {
+            let end = self.0[start..].find('}')?;
+            self.0[start..=start + end].parse().ok()
+        })
+    }
+}
+impl fmt::Display for ProfilePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl FromStr for ProfilePath {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for (start, _) in s.match_indices("${") {
+            let end = s[start..].find('}').ok_or(Error::BadMacro)?;
+            s[start..=start + end].parse::<Macro>()?;
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+impl Serialize for ProfilePath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for ProfilePath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
 //
 // Protocol
 //
@@ -815,6 +1293,10 @@ macro_rules! seccomp_error_action {
             Log,
             $( $act ),*
         }
+        impl SeccompErrorAction {
+            /// All values as they are written in a profile
+            pub const VALUES: &'static [&'static str] = &["kill", "log", $( stringify!($act) ),*];
+        }
         impl fmt::Display for SeccompErrorAction {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
                 match self {
@@ -836,6 +1318,18 @@ macro_rules! seccomp_error_action {
                 }
             }
         }
+        impl Serialize for SeccompErrorAction {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+        impl<'de> Deserialize<'de> for SeccompErrorAction {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(de::Error::custom)
+            }
+        }
     };
 }
 #[rustfmt::skip]
@@ -858,12 +1352,29 @@ seccomp_error_action! {
     ETXTBSY, EUCLEAN, EUNATCH, EUSERS, EWOULDBLOCK, EXDEV, EXFULL,
 }
 
+//
+// X11Server
+//
+
+values! {
+    /// A X11 server for firejails `x11` command
+    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+    pub enum X11Server {
+        Xephyr = "xephyr",
+        Xorg = "xorg",
+        Xpra = "xpra",
+        Xvfb = "xvfb",
+        _ = Error::BadX11,
+    }
+}
+
 //
 // Error
 //
 
 #[non_exhaustive]
-#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq)]
+#[derive(Clone, Debug, thiserror::Error, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Error {
     #[error("Invalid bind command")]
     BadBind,
@@ -874,13 +1385,134 @@ pub enum Error {
     #[error("Invalid condition")]
     BadCondition,
     #[error("Invalid dbus policy")]
+    #[serde(rename = "bad-dbus-policy")]
     BadDBusPolicy,
     #[error("Invalid env command")]
     BadEnv,
+    #[error("Invalid ignore command")]
+    BadIgnore,
+    #[error("Unknown macro")]
+    BadMacro,
     #[error("Invalid protocol")]
     BadProtocol,
     #[error("Invalid seccomp-error-action")]
     BadSeccompErrorAction,
+    #[error("Invalid x11 server")]
+    BadX11,
     #[error("No command after condition")]
     EmptyCondition,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = "\
+# Firejail profile for foo
+include foo.local
+include globals.local
+
+caps.drop all
+dbus-user filter
+dbus-user.talk org.freedesktop.Notifications
+private-bin foo,bar
+seccomp
+seccomp !chroot,mount
+?HAS_APPIMAGE: ignore private-dev
+";
+
+    #[test]
+    fn json_round_trip() {
+        let profile_stream = PROFILE.parse::<ProfileStream>().unwrap();
+        let json = serde_json::to_string(&profile_stream).unwrap();
+        let deserialized = serde_json::from_str::<ProfileStream>(&json).unwrap();
+        assert_eq!(deserialized.to_string(), PROFILE);
+    }
+
+    #[test]
+    fn deserialize_rejects_line_breaks() {
+        for json in [
+            r#"{"lines": [{"content": {"type": "command", "value":
+                {"command": "whitelist", "args": "/tmp\nnoblacklist ${HOME}/.ssh"}}}]}"#,
+            r#"{"lines": [{"content": {"type": "comment", "value": " foo\nnoroot"}}]}"#,
+            r#"{"lines": [{"content": {"type": "comment", "value": " foo\rnoroot"}}]}"#,
+            r#"{"lines": [{"content": {"type": "command", "value":
+                {"command": "ignore", "args": "noroot\nnet none"}}}]}"#,
+            r#"{"lines": [{"content": {"type": "command", "value":
+                {"command": "private-bin", "args": ["foo\nnoroot"]}}}]}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<ProfileStream>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+        let toml = "[[lines]]\n[lines.content]\ntype = \"comment\"\nvalue = \"a\\nnoroot\"\n";
+        assert!(toml::from_str::<ProfileStream>(toml).is_err());
+    }
+
+    #[test]
+    fn toml_round_trip() {
+        let profile_stream = PROFILE.parse::<ProfileStream>().unwrap();
+        let toml = toml::to_string(&profile_stream).unwrap();
+        let deserialized = toml::from_str::<ProfileStream>(&toml).unwrap();
+        assert_eq!(deserialized.to_string(), PROFILE);
+    }
+
+    #[test]
+    fn path_macros() {
+        let command = "whitelist ${HOME}/.config/${FOO}".parse::<Command>();
+        assert_eq!(command, Err(Error::BadMacro));
+
+        let path = "${RUNUSER}/foo-${HOME}".parse::<ProfilePath>().unwrap();
+        assert_eq!(
+            path.macros().collect::<Vec<_>>(),
+            vec![Macro::Runuser, Macro::Home]
+        );
+    }
+
+    #[test]
+    fn ignore_patterns() {
+        let full = "private-dev".parse::<IgnorePattern>().unwrap();
+        assert!(matches!(full, IgnorePattern::Command(_)));
+        let prefix = "noexec".parse::<IgnorePattern>().unwrap();
+        assert_eq!(prefix, IgnorePattern::Prefix("noexec".to_string()));
+        assert_eq!(
+            "private-dev ".parse::<IgnorePattern>(),
+            Err(Error::BadIgnore)
+        );
+        assert_eq!("foo bar".parse::<IgnorePattern>(), Err(Error::BadIgnore));
+
+        assert!(prefix.matches(&"noexec /tmp".parse().unwrap()));
+        assert!(full.matches(&Command::PrivateDev));
+        assert!(!"private"
+            .parse::<IgnorePattern>()
+            .unwrap()
+            .matches(&Command::PrivateDev));
+    }
+
+    #[test]
+    fn conditionals() {
+        let conditional = "?HAS_PRIVATE: private-cache"
+            .parse::<Conditional>()
+            .unwrap();
+        assert_eq!(conditional.condition(), Condition::HasPrivate);
+        assert_eq!(conditional.command(), &Command::PrivateCache);
+
+        let conditional = "?ALLOW_TRAY:\tnoroot".parse::<Conditional>().unwrap();
+        assert_eq!(conditional.to_string(), "?ALLOW_TRAY: noroot");
+
+        assert_eq!(
+            "?HAS_X11: ".parse::<Conditional>(),
+            Err(Error::EmptyCondition)
+        );
+        assert_eq!(
+            "?HAS_FOO: noroot".parse::<Conditional>(),
+            Err(Error::BadCondition)
+        );
+        assert_eq!(
+            "?HAS_X11 noroot".parse::<Conditional>(),
+            Err(Error::BadCondition)
+        );
+    }
+}
@@ -17,6 +17,7 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use crate::fatal;
 use crate::profile::{Profile, ProfileFlags};
 use log::{debug, error, trace};
 use std::fs::remove_file;
@@ -29,7 +30,7 @@ pub fn start(cli: &crate::cli::CliRm) {
             profile,
             ProfileFlags::LOOKUP_USER | ProfileFlags::DENY_BY_PATH | ProfileFlags::ASSUME_EXISTENCE,
         )
-        .unwrap();
+        .unwrap_or_else(|err| fatal!("{}", err));
         trace!("Deleting '{}'.", profile.full_name());
         remove_file(profile.path().unwrap())
             .unwrap_or_else(|err| error!("Failed to delete '{}': {}", profile.full_name(), err));
@@ -0,0 +1,70 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use crate::cli::CliFormat;
+use crate::config::CONFIG;
+use crate::profile::SHORTNAMES;
+use crate::utils::print_json;
+use log::debug;
+use serde_json::json;
+
+pub fn start(_cli: &crate::cli::CliShortnames, format: CliFormat) {
+    debug!("subcommand: shortnames");
+
+    let source = |short_name: &str| {
+        if CONFIG.shortnames.contains_key(short_name) {
+            "config"
+        } else {
+            "builtin"
+        }
+    };
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "shortnames": SHORTNAMES
+                .iter()
+                .map(|(short_name, long_name)| json!({
+                    "shortname": short_name,
+                    "name": long_name,
+                    "source": source(short_name),
+                }))
+                .collect::<Vec<_>>(),
+        }));
+        return;
+    }
+
+    let width = SHORTNAMES.keys().map(|s| s.len() + 1).max().unwrap_or(0);
+    for (short_name, long_name) in &*SHORTNAMES {
+        if source(short_name) == "config" {
+            println!(
+                "{:width$}  {} (config)",
+                format!("@{}", short_name),
+                long_name,
+                width = width
+            );
+        } else {
+            println!(
+                "{:width$}  {}",
+                format!("@{}", short_name),
+                long_name,
+                width = width
+            );
+        }
+    }
+}
@@ -41,6 +41,14 @@ macro_rules! fatal {
     }};
 }
 
+/// Serialize `value` as pretty printed JSON document to stdout.
+pub fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer_pretty(&mut stdout, value).unwrap();
+    stdout.write_all(b"\n").unwrap();
+}
+
 /// Python like `input()`.
 pub fn input(prompt: &str) -> io::Result<String> {
     let mut stdout = io::stdout();
@@ -161,6 +169,68 @@ where
     }
 }
 
+/// Returns all existing paths matching `pattern`
+///
+/// Like firejail (`glob(3)`), `*` and `?` match any characters of a single path component
+/// but not a leading `.`. A pattern without wildcards matches itself if it exists.
+pub fn glob(pattern: &path::Path) -> Vec<path::PathBuf> {
+    let mut paths = vec![path::PathBuf::new()];
+    for component in pattern.components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !component.contains(|c| c == '*' || c == '?') {
+            for path in &mut paths {
+                path.push(&*component);
+            }
+            continue;
+        }
+
+        paths = paths
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok().map(|entries| (dir, entries)))
+            .flat_map(|(dir, entries)| {
+                let mut names = entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .filter(|name| !name.starts_with('.') || component.starts_with('.'))
+                    .filter(|name| wildcard_match(&component, name))
+                    .collect::<Vec<_>>();
+                names.sort_unstable();
+                names.into_iter().map(move |name| dir.join(name))
+            })
+            .collect();
+    }
+
+    paths.retain(|path| path.symlink_metadata().is_ok());
+    paths
+}
+
+/// Check whether `name` matches `pattern`, where `*` matches any number of characters
+/// and `?` matches exactly one character.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in pattern and the position in name it was tried at.
+    let mut backtrack = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 //
 // ColoredText
 //
@@ -180,7 +250,11 @@ impl ColoredText {
     pub fn new(color: termcolor::Color, text: impl AsRef<str>) -> Self {
         use termcolor::{Buffer, ColorSpec, WriteColor};
 
-        let mut buffer = Buffer::ansi();
+        let mut buffer = if crate::config::CONFIG.use_color() {
+            Buffer::ansi()
+        } else {
+            Buffer::no_color()
+        };
         buffer
             .set_color(ColorSpec::new().set_fg(Some(color)))
             .unwrap();
@@ -238,6 +312,43 @@ impl AsRef<str> for ColoredText {
     }
 }
 
+/// A temporary directory for tests, it is removed on drop
+#[cfg(test)]
+pub struct TempDir(path::PathBuf);
+#[cfg(test)]
+impl TempDir {
+    pub fn new() -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "fjp-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &path::Path {
+        &self.0
+    }
+
+    /// Write `content` to `name` inside this directory, missing parents are created
+    pub fn write(&self, name: &str, content: &str) -> path::PathBuf {
+        let file = self.0.join(name);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, content).unwrap();
+        file
+    }
+}
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,4 +381,15 @@ mod tests {
     fn test_get_name1_dotdot_in_name() {
         get_name1("./../forbidden");
     }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "foo"));
+        assert!(wildcard_match("foo*", "foo"));
+        assert!(wildcard_match("*.desktop", "foo.desktop"));
+        assert!(wildcard_match("f?o*bar", "fooxbazbar"));
+        assert!(!wildcard_match("foo", "foobar"));
+        assert!(!wildcard_match("*.desktop", "foo.desktop~"));
+        assert!(!wildcard_match("f?o", "fo"));
+    }
 }
@@ -0,0 +1,418 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for validating profile files, e.g. in CI
+
+use crate::cli::{CliFormat, CliValidateFormat};
+use crate::conditions::Evaluator;
+use crate::effective::{effective_profile_in, EffectiveLine, LOOKUP_FLAGS};
+use crate::profile::{Profile, ProfileFlags};
+use crate::profile_stream::{Command, Content, ProfileStream};
+use crate::utils::print_json;
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::process::exit;
+use std::sync::Arc;
+
+/// The rule id for includes which can not be found
+const MISSING_INCLUDE: &str = "missing-include";
+/// The rule id for `ignore` lines which do not suppress any line
+const UNUSED_IGNORE: &str = "unused-ignore";
+
+/// A problem found in a profile file
+#[derive(Debug, Serialize)]
+struct Problem {
+    file: String,
+    /// 1-based line number
+    line: usize,
+    rule: String,
+    #[serde(skip)]
+    rule_description: String,
+    message: String,
+}
+
+pub fn start(cli: &crate::cli::CliValidate, format: CliFormat) {
+    debug!("subcommand: validate");
+
+    let mut problems = Vec::new();
+    for file in &cli.files {
+        match read_to_string(file) {
+            Ok(data) => validate(file, &data, &mut problems),
+            Err(err) => {
+                error!("Failed to read '{}': {}", file, err);
+                exit(1);
+            }
+        }
+    }
+
+    let report_format = cli.format.unwrap_or(match format {
+        CliFormat::Text => CliValidateFormat::Text,
+        CliFormat::Json => CliValidateFormat::Json,
+    });
+    if report_format == CliValidateFormat::Sarif {
+        print_json(&sarif(&problems));
+    } else if report_format == CliValidateFormat::Json {
+        print_json(&json!({ "problems": problems }));
+    } else if problems.is_empty() {
+        info!("No problems found.");
+    } else {
+        for problem in &problems {
+            println!(
+                "{}:{}: {} [{}]",
+                problem.file, problem.line, problem.message, problem.rule
+            );
+        }
+    }
+
+    exit(if problems.is_empty() { 0 } else { 1 });
+}
+
+fn validate(file: &str, data: &str, problems: &mut Vec<Problem>) {
+    let profile_stream = data.parse::<ProfileStream>().unwrap_or_else(|ps| ps);
+    let problem_count = problems.len();
+    for line in &profile_stream {
+        let lineno = line.lineno.map_or(0, |lineno| lineno + 1);
+        match &*line.content {
+            Content::Invalid(invalid, err) => problems.push(Problem {
+                file: file.to_string(),
+                line: lineno,
+                rule: serde_json::to_value(err)
+                    .ok()
+                    .and_then(|id| id.as_str().map(String::from))
+                    .unwrap_or_default(),
+                rule_description: err.to_string(),
+                message: format!("{}: {}", err, invalid),
+            }),
+            Content::Command(Command::Include(include)) if !include_exists(file, include) => {
+                problems.push(Problem {
+                    file: file.to_string(),
+                    line: lineno,
+                    rule: MISSING_INCLUDE.to_string(),
+                    rule_description: "Included file not found".to_string(),
+                    message: format!("Included file not found: {}", include),
+                })
+            }
+            _ => (),
+        }
+    }
+
+    // The include chain is incomplete if any include is missing, then the lines an
+    // ignore is meant for might be missing too. The ignores of .inc and .local files
+    // are meant for the profiles including them.
+    if file.ends_with(".profile")
+        && !problems[problem_count..]
+            .iter()
+            .any(|problem| problem.rule == MISSING_INCLUDE)
+    {
+        check_unused_ignores(file, problems);
+    }
+}
+
+/// Report the `ignore` lines of the profile `file` which do not suppress any line in its
+/// include chain
+///
+/// Like [`include_exists`], includes are searched in the directory of `file` first.
+fn check_unused_ignores(file: &str, problems: &mut Vec<Problem>) {
+    let path = Path::new(file);
+    let evaluator = Evaluator::from_config();
+    let lines = match effective_profile_in(
+        path,
+        path.parent().unwrap_or_else(|| Path::new(".")),
+        &evaluator,
+    ) {
+        Ok(lines) => lines,
+        Err(err) => {
+            warn!("Can not check the ignores of '{}': {}", file, err);
+            return;
+        }
+    };
+    report_unused_ignores(file, &lines, &evaluator, problems);
+}
+
+/// Report the `ignore` lines from the first file of `lines` which suppress no line
+///
+/// Conditional ignores are only reported if `evaluator` says their condition is true.
+fn report_unused_ignores(
+    file: &str,
+    lines: &[EffectiveLine],
+    evaluator: &Evaluator,
+    problems: &mut Vec<Problem>,
+) {
+    for (idx, line) in lines.iter().enumerate() {
+        if !Arc::ptr_eq(&line.path, &lines[0].path) {
+            continue;
+        }
+        if !evaluator.applies(&line.line.content) {
+            continue;
+        }
+        if let Some(Command::Ignore(pattern)) = line.command() {
+            if !lines.iter().any(|line| line.ignored_by == Some(idx)) {
+                problems.push(Problem {
+                    file: file.to_string(),
+                    line: line.line.lineno.map_or(0, |lineno| lineno + 1),
+                    rule: UNUSED_IGNORE.to_string(),
+                    rule_description: "Ignore without effect".to_string(),
+                    message: format!("`ignore {}` does not ignore any line", pattern),
+                });
+            }
+        }
+    }
+}
+
+/// Check whether `include` from `file` exists
+///
+/// Like firejail, missing `.local`s are fine. Besides the profile search path,
+/// the directory of `file` is searched, so a repository of profiles can be validated.
+/// Includes with macros (e.g. `${CFG}`) can not be checked and are assumed to exist.
+pub fn include_exists(file: &str, include: &str) -> bool {
+    if include.ends_with(".local") || include.contains('$') {
+        return true;
+    }
+    if include.contains('/') {
+        return Path::new(include).exists();
+    }
+    if let Some(dir) = Path::new(file).parent() {
+        if dir.join(include).exists() {
+            return true;
+        }
+    }
+    Profile::new(include, LOOKUP_FLAGS.without(ProfileFlags::READ))
+        .map_or(false, |profile| profile.path().is_some())
+}
+
+/// Build a SARIF 2.1.0 log from `problems`
+fn sarif(problems: &[Problem]) -> serde_json::Value {
+    let rules = problems
+        .iter()
+        .map(|problem| (problem.rule.as_str(), problem.rule_description.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "fjp",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_HOMEPAGE"),
+                    "rules": rules
+                        .iter()
+                        .map(|(id, description)| json!({
+                            "id": id,
+                            "shortDescription": { "text": description },
+                        }))
+                        .collect::<Vec<_>>(),
+                },
+            },
+            "results": problems
+                .iter()
+                .map(|problem| json!({
+                    "ruleId": problem.rule,
+                    "level": "error",
+                    "message": { "text": problem.message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": artifact_location(&problem.file),
+                            "region": { "startLine": problem.line },
+                        },
+                    }],
+                }))
+                .collect::<Vec<_>>(),
+        }],
+    })
+}
+
+/// The SARIF `artifactLocation` of `file`
+///
+/// Absolute paths are `file://` URIs, relative paths are relative to `%SRCROOT%`.
+fn artifact_location(file: &str) -> serde_json::Value {
+    let uri = percent_encode_path(file.strip_prefix("./").unwrap_or(file));
+    if uri.starts_with('/') {
+        json!({ "uri": format!("file://{}", uri) })
+    } else {
+        json!({ "uri": uri, "uriBaseId": "%SRCROOT%" })
+    }
+}
+
+/// Percent-encode every byte of `path` except the unreserved characters of RFC 3986 and `/`
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effective::effective_profile_from;
+    use crate::profile_stream::Condition;
+    use crate::utils::TempDir;
+
+    fn unused_ignores(data: &str, files: &[(&str, &str)], evaluator: &Evaluator) -> Vec<usize> {
+        let mut problems = Vec::new();
+        report_unused_ignores(
+            "test.profile",
+            &effective_profile_from(data, files, evaluator),
+            evaluator,
+            &mut problems,
+        );
+        problems.iter().map(|problem| problem.line).collect()
+    }
+
+    #[test]
+    fn test_unused_ignores() {
+        assert_eq!(
+            unused_ignores(
+                "ignore noroot\nignore nosound\ninclude foo.inc\n",
+                &[("foo.inc", "noroot\n")],
+                &Evaluator::default()
+            ),
+            [2]
+        );
+    }
+
+    #[test]
+    fn test_unused_ignores_conditional() {
+        // A conditional ignore with a false condition neither suppresses a line nor
+        // is reported.
+        let profile = "?HAS_X11: ignore noroot\n?HAS_X11: ignore nosound\nnoroot\n";
+        let evaluator = Evaluator::default();
+        let lines = effective_profile_from(profile, &[], &evaluator);
+        assert_eq!(lines[2].ignored_by, None);
+        assert!(unused_ignores(profile, &[], &evaluator).is_empty());
+
+        let evaluator = Evaluator::new(&Default::default(), [Condition::HasX11]);
+        let lines = effective_profile_from(profile, &[], &evaluator);
+        assert_eq!(lines[2].ignored_by, Some(0));
+        assert_eq!(unused_ignores(profile, &[], &evaluator), [2]);
+    }
+
+    #[test]
+    fn test_unused_ignores_only_for_profiles() {
+        let mut problems = Vec::new();
+        validate("foo.local", "ignore noroot\n", &mut problems);
+        validate("foo.inc", "ignore noroot\n", &mut problems);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn test_artifact_location() {
+        assert_eq!(
+            artifact_location("./firefox.profile"),
+            json!({ "uri": "firefox.profile", "uriBaseId": "%SRCROOT%" })
+        );
+        assert_eq!(
+            artifact_location("/etc/firejail/firefox.profile"),
+            json!({ "uri": "file:///etc/firejail/firefox.profile" })
+        );
+    }
+
+    #[test]
+    fn test_percent_encode_path() {
+        assert_eq!(percent_encode_path("a-b_c.d~e/f"), "a-b_c.d~e/f");
+        assert_eq!(
+            percent_encode_path("my profiles/#1?[x]%.inc"),
+            "my%20profiles/%231%3F%5Bx%5D%25.inc"
+        );
+        assert_eq!(percent_encode_path("ä.profile"), "%C3%A4.profile");
+    }
+
+    #[test]
+    fn test_sarif() {
+        let problems = [
+            Problem {
+                file: "./foo.profile".to_string(),
+                line: 3,
+                rule: MISSING_INCLUDE.to_string(),
+                rule_description: "Included file not found".to_string(),
+                message: "Included file not found: bar.inc".to_string(),
+            },
+            Problem {
+                file: "./foo.profile".to_string(),
+                line: 5,
+                rule: UNUSED_IGNORE.to_string(),
+                rule_description: "Ignore without effect".to_string(),
+                message: "`ignore nosound` does not ignore any line".to_string(),
+            },
+        ];
+        let log = sarif(&problems);
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "fjp");
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([
+                {
+                    "id": MISSING_INCLUDE,
+                    "shortDescription": { "text": "Included file not found" },
+                },
+                {
+                    "id": UNUSED_IGNORE,
+                    "shortDescription": { "text": "Ignore without effect" },
+                },
+            ])
+        );
+        assert_eq!(
+            run["results"][1],
+            json!({
+                "ruleId": UNUSED_IGNORE,
+                "level": "error",
+                "message": { "text": "`ignore nosound` does not ignore any line" },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "foo.profile", "uriBaseId": "%SRCROOT%" },
+                        "region": { "startLine": 5 },
+                    },
+                }],
+            })
+        );
+        assert_eq!(run["results"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unused_ignores_with_includes_from_the_same_dir() {
+        let tmp = TempDir::new();
+        tmp.write("repo-only.inc", "noroot\n");
+        let data = "ignore noroot\nignore nosound\ninclude repo-only.inc\n";
+        let file = tmp.write("a.profile", data);
+        let file = file.to_str().unwrap();
+
+        let mut problems = Vec::new();
+        validate(file, data, &mut problems);
+        assert_eq!(
+            problems
+                .iter()
+                .map(|problem| (problem.line, problem.rule.as_str()))
+                .collect::<Vec<_>>(),
+            [(2, UNUSED_IGNORE)]
+        );
+    }
+}
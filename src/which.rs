@@ -0,0 +1,295 @@
+/*
+ * Copyright © 2020-2022 The fjp Authors
+ *
+ * This file is part of fjp
+ *
+ * fjp is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * fjp is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Module for simulating which profile firejail selects for a program
+
+use crate::cli::CliFormat;
+use crate::conditions::Evaluator;
+use crate::effective::{effective_profile, EffectiveLine, LOOKUP_FLAGS};
+use crate::fatal;
+use crate::profile::{Profile, ProfileFlags};
+use crate::utils::{print_json, ColoredText};
+use log::debug;
+use nix::unistd::getuid;
+use serde_json::json;
+use std::env::{split_paths, var_os};
+use std::ffi::OsStr;
+use std::fs::read_link;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use termcolor::Color;
+
+/// Suffixes firejail removes from the command name
+const STRIPPED_SUFFIXES: &[&str] = &[".AppImage", ".appimage"];
+
+pub fn start(cli: &crate::cli::CliWhich, format: CliFormat) {
+    debug!("subcommand: which");
+
+    let (program, firecfg_symlink) = resolve_program(&cli.program);
+
+    let name = profile_name(&cli.program);
+
+    let fallback = fallback_profile(getuid().is_root());
+    let find = |name: &str| {
+        Profile::new(name, LOOKUP_FLAGS.without(ProfileFlags::READ))
+            .ok()
+            .and_then(|profile| profile.path().map(Path::to_path_buf))
+    };
+    let (profile, is_fallback) = select_profile(name, fallback, find)
+        .unwrap_or_else(|| fatal!("Neither {}.profile nor {} exist.", name, fallback));
+
+    let chain = match effective_profile(&profile.to_string_lossy(), &Evaluator::from_config()) {
+        Ok(lines) => profile_chain(&lines),
+        Err(err) => fatal!("{}", err),
+    };
+
+    if format == CliFormat::Json {
+        print_json(&json!({
+            "program": program,
+            "firecfg_symlink": firecfg_symlink,
+            "name": name,
+            "profile": profile,
+            "fallback": is_fallback,
+            "chain": chain
+                .iter()
+                .map(|(path, depth)| json!({ "path": path, "depth": depth }))
+                .collect::<Vec<_>>(),
+        }));
+        return;
+    }
+
+    match &program {
+        Some(program) => println!("program: {}", program.display()),
+        None => println!("program: {} (not found)", cli.program),
+    }
+    if let Some(symlink) = &firecfg_symlink {
+        println!("firecfg: {} -> firejail", symlink.display());
+    }
+    if is_fallback {
+        println!(
+            "profile: {} (no {}.profile found)",
+            ColoredText::new(Color::Yellow, profile.to_string_lossy()),
+            name
+        );
+    } else {
+        println!(
+            "profile: {}",
+            ColoredText::new(Color::Green, profile.to_string_lossy())
+        );
+    }
+    for (path, depth) in &chain {
+        println!(
+            "{:indent$}{}",
+            "",
+            path.display(),
+            indent = 2 * usize::from(*depth) + 2
+        );
+    }
+}
+
+/// The profile firejail uses if there is no profile for the program
+fn fallback_profile(is_root: bool) -> &'static str {
+    if is_root {
+        "server.profile"
+    } else {
+        "default.profile"
+    }
+}
+
+/// Select the profile for `name` like firejail, `NAME.profile` or else `fallback`
+///
+/// `find` returns the path of a profile if it exists. Returns the path of the selected
+/// profile and whether it is the fallback.
+fn select_profile<F>(name: &str, fallback: &str, find: F) -> Option<(PathBuf, bool)>
+where
+    F: Fn(&str) -> Option<PathBuf>,
+{
+    match find(&format!("{}.profile", name)) {
+        Some(path) => Some((path, false)),
+        None => find(fallback).map(|path| (path, true)),
+    }
+}
+
+/// The chain of `.profile` files which are loaded for `lines` together with their
+/// include depth, e.g. a redirect profile and its target
+fn profile_chain(lines: &[EffectiveLine]) -> Vec<(PathBuf, u8)> {
+    let mut chain: Vec<(PathBuf, u8)> = Vec::new();
+    for line in lines {
+        if line.path.extension().map_or(false, |ext| ext == "profile")
+            && !chain.iter().any(|(path, _)| **line.path == *path)
+        {
+            chain.push((line.path.to_path_buf(), line.depth));
+        }
+    }
+    chain
+}
+
+/// The name of the profile firejail selects for `program`, without `.profile`
+///
+/// Like firejail, the profile is selected by the name of the command, not the path it
+/// resolves to.
+pub fn profile_name(program: &str) -> &str {
+    let basename = program.rsplit('/').next().unwrap_or_default();
+    STRIPPED_SUFFIXES
+        .iter()
+        .find_map(|suffix| basename.strip_suffix(suffix))
+        .unwrap_or(basename)
+}
+
+/// Resolve `program` through `PATH`
+///
+/// Returns the program and the symlink to firejail created by firecfg which was skipped.
+pub fn resolve_program(program: &str) -> (Option<PathBuf>, Option<PathBuf>) {
+    resolve_program_in(program, &var_os("PATH").unwrap_or_default())
+}
+
+/// Like [`resolve_program`] with `path` instead of `PATH`
+fn resolve_program_in(program: &str, path: &OsStr) -> (Option<PathBuf>, Option<PathBuf>) {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_firejail_symlink(&path) {
+            (None, Some(path))
+        } else if path.exists() {
+            (Some(path), None)
+        } else {
+            (None, None)
+        };
+    }
+
+    let mut firecfg_symlink = None;
+    for candidate in split_paths(path).map(|dir| dir.join(program)) {
+        if is_firejail_symlink(&candidate) {
+            firecfg_symlink.get_or_insert(candidate);
+        } else if is_executable(&candidate) {
+            return (Some(candidate), firecfg_symlink);
+        }
+    }
+    (None, firecfg_symlink)
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata().map_or(false, |metadata| {
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+}
+
+/// Check whether `path` is a symlink to firejail, like the ones firecfg creates
+pub fn is_firejail_symlink(path: &Path) -> bool {
+    read_link(path).map_or(false, |target| {
+        target.file_name().map_or(false, |name| name == "firejail")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effective::effective_profile_from;
+    use crate::utils::TempDir;
+    use std::env::join_paths;
+    use std::fs::{create_dir_all, set_permissions, Permissions};
+    use std::os::unix::fs::symlink;
+
+    fn write_executable(tmp: &TempDir, name: &str) -> PathBuf {
+        let path = tmp.write(name, "#!/bin/sh\n");
+        set_permissions(&path, Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_profile_name() {
+        assert_eq!(profile_name("firefox"), "firefox");
+        assert_eq!(profile_name("/usr/bin/firefox"), "firefox");
+        assert_eq!(profile_name("./Foo.AppImage"), "Foo");
+        assert_eq!(profile_name("bar.appimage"), "bar");
+        assert_eq!(profile_name("baz.AppImage.sh"), "baz.AppImage.sh");
+    }
+
+    #[test]
+    fn test_resolve_program() {
+        let tmp = TempDir::new();
+        create_dir_all(tmp.path().join("local")).unwrap();
+        symlink("/usr/bin/firejail", tmp.path().join("local/foo")).unwrap();
+        let foo = write_executable(&tmp, "bin/foo");
+        tmp.write("bin/not-executable", "");
+        // A symlink into a fixture dir is a regular program.
+        let bar = write_executable(&tmp, "fixture/bar");
+        symlink(&bar, tmp.path().join("local/bar")).unwrap();
+        let path = join_paths([tmp.path().join("local"), tmp.path().join("bin")]).unwrap();
+
+        assert_eq!(
+            resolve_program_in("foo", &path),
+            (Some(foo), Some(tmp.path().join("local/foo")))
+        );
+        assert_eq!(
+            resolve_program_in("bar", &path),
+            (Some(tmp.path().join("local/bar")), None)
+        );
+        assert_eq!(resolve_program_in("not-executable", &path), (None, None));
+        assert_eq!(resolve_program_in("missing", &path), (None, None));
+
+        let foo = tmp.path().join("local/foo");
+        assert_eq!(
+            resolve_program_in(foo.to_str().unwrap(), &path),
+            (None, Some(foo))
+        );
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let find = |name: &str| {
+            ["foo.profile", "default.profile", "server.profile"]
+                .contains(&name)
+                .then(|| PathBuf::from(name))
+        };
+        assert_eq!(
+            select_profile("foo", fallback_profile(false), find),
+            Some((PathBuf::from("foo.profile"), false))
+        );
+        assert_eq!(
+            select_profile("bar", fallback_profile(false), find),
+            Some((PathBuf::from("default.profile"), true))
+        );
+        assert_eq!(
+            select_profile("bar", fallback_profile(true), find),
+            Some((PathBuf::from("server.profile"), true))
+        );
+        assert_eq!(select_profile("bar", "missing.profile", find), None);
+    }
+
+    #[test]
+    fn test_profile_chain() {
+        let lines = effective_profile_from(
+            "include foo.local\ninclude bar.profile\n",
+            &[
+                ("foo.local", "include extra.profile\n"),
+                ("extra.profile", "noroot\n"),
+                ("bar.profile", "include extra.profile\n"),
+            ],
+            &Evaluator::default(),
+        );
+        assert_eq!(
+            profile_chain(&lines),
+            [
+                (PathBuf::from("test.profile"), 0),
+                (PathBuf::from("extra.profile"), 2),
+                (PathBuf::from("bar.profile"), 1),
+            ]
+        );
+    }
+}